@@ -0,0 +1,103 @@
+// Compare Flib::predict() with the compiled Scorer on a large population.
+//
+// "compile-and-score" compiles the whole population each time, while
+// "generation" is what simulate() does once per generation: recompile the
+// two flibs that changed and score everyone.  "window" scores with a
+// windowed horizon, which the Scorer can't compile, and "window-cached"
+// looks every flib up in a warm fitness cache instead, as simulate() does
+// for flibs that haven't changed.  The "batch" entries score a precompiled
+// batch::Batch with and without SIMD.
+//
+// Run with `cargo bench --bench scoring`.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
+use rand::{Rng, SeedableRng, StdRng};
+
+use autosoup::batch::Batch;
+use autosoup::cache::FitnessCache;
+use autosoup::compiled::{EncodedEnv, Scorer};
+use autosoup::{make_from_chromosome, Flib, Horizon};
+
+fn random_population(size: usize, num_states: usize, rng: &mut StdRng) -> Vec<Flib> {
+    (0..size)
+        .map(|_| {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(num_states, rng);
+            flib
+        })
+        .collect()
+}
+
+fn bench_scoring(c: &mut Criterion) {
+    let mut rng = StdRng::from_seed(&[2021][..]);
+    let environment: String = (0..64).map(|_| if rng.gen() { '1' } else { '0' }).collect();
+
+    let mut group = c.benchmark_group("score_population");
+    for &size in &[100, 1000, 10000] {
+        let mut population = random_population(size, 16, &mut rng);
+
+        group.bench_with_input(BenchmarkId::new("predict", size), &size, |b, _| {
+            b.iter(|| {
+                population
+                    .iter_mut()
+                    .map(|flib| flib.predict(black_box(&environment)))
+                    .collect::<Vec<f32>>()
+            })
+        });
+
+        let mut scorer = Scorer::new(&environment).unwrap();
+        let mut scores = Vec::with_capacity(size);
+        group.bench_with_input(
+            BenchmarkId::new("compile-and-score", size),
+            &size,
+            |b, _| b.iter(|| scorer.score_population(black_box(&population), &mut scores)),
+        );
+
+        scorer.compile(&population);
+        group.bench_with_input(BenchmarkId::new("generation", size), &size, |b, _| {
+            b.iter(|| {
+                scorer.update(0, black_box(&population[0]));
+                scorer.update(size - 1, black_box(&population[size - 1]));
+                scorer.scores(&mut scores)
+            })
+        });
+
+        let window = Horizon::Window(4);
+        group.bench_with_input(BenchmarkId::new("window", size), &size, |b, _| {
+            b.iter(|| {
+                population
+                    .iter_mut()
+                    .map(|flib| flib.predict_horizon(black_box(&environment), window))
+                    .collect::<Vec<f32>>()
+            })
+        });
+
+        let mut cache = FitnessCache::new(size);
+        group.bench_with_input(BenchmarkId::new("window-cached", size), &size, |b, _| {
+            b.iter(|| {
+                population
+                    .iter_mut()
+                    .map(|flib| {
+                        cache.score(flib, black_box(&environment), window, |flib| {
+                            flib.predict_horizon(&environment, window)
+                        })
+                    })
+                    .collect::<Vec<f32>>()
+            })
+        });
+
+        let env = EncodedEnv::new(&environment).unwrap();
+        for (name, batch) in &[
+            ("batch-scalar", Batch::scalar(&population)),
+            ("batch-simd", Batch::new(&population)),
+        ] {
+            group.bench_with_input(BenchmarkId::new(*name, size), &size, |b, _| {
+                b.iter(|| batch.scores(black_box(&env), &mut scores))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, bench_scoring);
+criterion_main!(benches);
@@ -0,0 +1,279 @@
+// Batched evaluation: run many flibs in lockstep over one environment.
+//
+// The population is compiled once into blocks of LANES flibs, with the
+// table laid out structure-of-arrays style: entry (state, input) of every
+// flib in a block sits in one run of LANES consecutive slots, so a single
+// step of the whole block reads LANES neighbouring words.  Each block is
+// padded out to its largest flib.  Unlike the Scorer's byte table, the
+// entries are 32 bits wide, since that's what an AVX2 gather loads.
+//
+// On x86-64 CPUs with AVX2 a block is stepped with one gather per symbol;
+// anywhere else, or when asked for, a plain scalar loop does the same
+// thing.  Both give exactly the same scores as Flib::predict().  Each
+// step depends on the one before it, so GROUP blocks are run side by side
+// to keep several gathers in flight at once.
+
+use crate::compiled::EncodedEnv;
+use crate::Flib;
+
+// Flibs per block, which is the number of 32-bit lanes in an AVX2
+// register.
+const LANES: usize = 8;
+
+// Blocks run together.
+const GROUP: usize = 4;
+
+// Output code for anything other than '0' or '1'; it never matches.
+const NO_MATCH: u32 = 2;
+
+#[derive(Debug)]
+pub struct Batch {
+    table: Vec<u32>,
+    // Offset of each block in `table`, plus one past the end.
+    blocks: Vec<usize>,
+    len: usize,
+    simd: bool,
+}
+
+impl Batch {
+    // Compile `population`, using SIMD if this CPU supports it.  Every flib
+    // needs at least one state.
+    pub fn new(population: &[Flib]) -> Batch {
+        Batch::compile(population, simd_available())
+    }
+
+    // Compile `population` for the scalar path only.
+    pub fn scalar(population: &[Flib]) -> Batch {
+        Batch::compile(population, false)
+    }
+
+    fn compile(population: &[Flib], simd: bool) -> Batch {
+        let mut table = vec![];
+        let mut blocks = vec![0];
+        assert!(
+            population.iter().all(|f| !f.states.is_empty()),
+            "can't compile a flib with no states"
+        );
+        for flibs in population.chunks(LANES) {
+            let max_states = flibs.iter().map(|f| f.states.len()).max().unwrap_or(0);
+            let block_len = 2 * max_states * LANES;
+            assert!(block_len < (1 << 30), "too many states to compile");
+            let start = table.len();
+            // Unused slots are zero, which loops on state 0 with output 0.
+            table.resize(start + block_len, 0);
+            let block = &mut table[start..];
+            for (lane, flib) in flibs.iter().enumerate() {
+                compile_lane(flib, lane, block);
+            }
+            blocks.push(table.len());
+        }
+        Batch {
+            table,
+            blocks,
+            len: population.len(),
+            simd,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // Whether scores() uses the SIMD path.
+    pub fn uses_simd(&self) -> bool {
+        self.simd
+    }
+
+    // Score every flib against `env` into `scores`, which is cleared
+    // first.
+    pub fn scores(&self, env: &EncodedEnv, scores: &mut Vec<f32>) {
+        scores.clear();
+        let steps = env.num_steps() as f32;
+        let num_blocks = self.blocks.len() - 1;
+        for first in (0..num_blocks).step_by(GROUP) {
+            // Short groups rerun their first block.
+            let in_group = (num_blocks - first).min(GROUP);
+            let mut group = [self.block(first); GROUP];
+            for (b, block) in group.iter_mut().enumerate().take(in_group) {
+                *block = self.block(first + b);
+            }
+            let matches = self.run_group(&group, env);
+            for block_matches in &matches[..in_group] {
+                let in_block = (self.len - scores.len()).min(LANES);
+                scores.extend(
+                    block_matches[..in_block]
+                        .iter()
+                        .map(|&m| (m as f32) / steps),
+                );
+            }
+        }
+    }
+
+    // The compiled table of block `index`.
+    fn block(&self, index: usize) -> &[u32] {
+        &self.table[self.blocks[index]..self.blocks[index + 1]]
+    }
+
+    fn run_group(&self, blocks: &[&[u32]; GROUP], env: &EncodedEnv) -> [[u32; LANES]; GROUP] {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if self.simd {
+                // Safe because `simd` is only set once AVX2 has been
+                // detected, and compile_lane() keeps every entry inside
+                // its block.
+                return unsafe { run_group_avx2(blocks, env) };
+            }
+        }
+        run_group_scalar(blocks, env)
+    }
+}
+
+fn simd_available() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        is_x86_feature_detected!("avx2")
+    }
+    #[cfg(not(target_arch = "x86_64"))]
+    {
+        false
+    }
+}
+
+// Write `flib` into lane `lane` of `block`.  Each entry packs the offset of
+// the destination state's row above a two-bit output code; a row holds
+// input 0's LANES slots followed by input 1's.
+fn compile_lane(flib: &Flib, lane: usize, block: &mut [u32]) {
+    let num_states = flib.states.len();
+    for (state, transitions) in flib.states.iter().enumerate() {
+        for (input, &(output, dest)) in transitions.iter().enumerate() {
+            assert!(dest < num_states, "transition to a missing state");
+            let code = match output {
+                '0' => 0,
+                '1' => 1,
+                _ => NO_MATCH,
+            };
+            let slot = (2 * state + input) * LANES + lane;
+            block[slot] = (((2 * dest * LANES) as u32) << 2) | code;
+        }
+    }
+}
+
+fn run_group_scalar(blocks: &[&[u32]; GROUP], env: &EncodedEnv) -> [[u32; LANES]; GROUP] {
+    let mut row = [[0u32; LANES]; GROUP];
+    let mut matches = [[0u32; LANES]; GROUP];
+    for (input, expected) in env.steps() {
+        let input = u32::from(input) * LANES as u32;
+        let expected = u32::from(expected);
+        for b in 0..GROUP {
+            for lane in 0..LANES {
+                let entry = blocks[b][(row[b][lane] + input) as usize + lane];
+                matches[b][lane] += ((entry & 3) == expected) as u32;
+                row[b][lane] = entry >> 2;
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(target_arch = "x86_64")]
+#[target_feature(enable = "avx2")]
+unsafe fn run_group_avx2(blocks: &[&[u32]; GROUP], env: &EncodedEnv) -> [[u32; LANES]; GROUP] {
+    use std::arch::x86_64::*;
+
+    let lanes = _mm256_setr_epi32(0, 1, 2, 3, 4, 5, 6, 7);
+    let code_mask = _mm256_set1_epi32(3);
+    let mut row = [_mm256_setzero_si256(); GROUP];
+    let mut matches = [_mm256_setzero_si256(); GROUP];
+    for (input, expected) in env.steps() {
+        // The lane offsets are folded into the input offset.
+        let input = _mm256_add_epi32(_mm256_set1_epi32(i32::from(input) * LANES as i32), lanes);
+        let expected = _mm256_set1_epi32(i32::from(expected));
+        for b in 0..GROUP {
+            let index = _mm256_add_epi32(row[b], input);
+            let entry = _mm256_i32gather_epi32::<4>(blocks[b].as_ptr() as *const i32, index);
+            // A matching lane compares as -1, so subtracting counts it.
+            let hit = _mm256_cmpeq_epi32(_mm256_and_si256(entry, code_mask), expected);
+            matches[b] = _mm256_sub_epi32(matches[b], hit);
+            row[b] = _mm256_srli_epi32::<2>(entry);
+        }
+    }
+
+    let mut counts = [[0u32; LANES]; GROUP];
+    for b in 0..GROUP {
+        _mm256_storeu_si256(counts[b].as_mut_ptr() as *mut __m256i, matches[b]);
+    }
+    counts
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_from_chromosome;
+    use rand::{Rng, SeedableRng, StdRng};
+
+    #[test]
+    fn test_compile_lane() {
+        let flib = make_from_chromosome(String::from("0B1B1A0A"));
+        let batch = Batch::scalar(&[flib]);
+        assert_eq!(batch.table.len(), 4 * LANES);
+        // State 0, input 1 outputs 1 and moves to state 1's row.
+        assert_eq!(batch.table[LANES], ((2 * LANES as u32) << 2) | 1);
+        assert_eq!(batch.table[2 * LANES], 1);
+    }
+
+    #[test]
+    fn test_matches_predict() {
+        let mut rng = StdRng::from_seed(&[32][..]);
+        let mut scores = vec![];
+        for _ in 0..50 {
+            let length = rng.gen_range(1, 20);
+            let environment: String = (0..length)
+                .map(|_| if rng.gen() { '1' } else { '0' })
+                .collect();
+            let env = EncodedEnv::new(&environment).unwrap();
+            // Mixed sizes within a block, and partly-filled last blocks.
+            let size = rng.gen_range(1, 30);
+            let mut population: Vec<Flib> = (0..size)
+                .map(|_| {
+                    let mut flib = make_from_chromosome(String::new());
+                    flib.randomize(rng.gen_range(1, 10), &mut rng);
+                    flib
+                })
+                .collect();
+            let predicted: Vec<f32> = population
+                .iter_mut()
+                .map(|f| f.predict(&environment))
+                .collect();
+
+            Batch::scalar(&population).scores(&env, &mut scores);
+            assert_eq!(scores, predicted);
+            // On CPUs without AVX2 this is the scalar path again.
+            Batch::new(&population).scores(&env, &mut scores);
+            assert_eq!(scores, predicted);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "no states")]
+    fn test_stateless_flib() {
+        // Its block would be empty, and the gather would read past it.
+        let flibs = [
+            make_from_chromosome(String::from("0A1A")),
+            make_from_chromosome(String::new()),
+        ];
+        Batch::new(&flibs);
+    }
+
+    #[test]
+    fn test_empty_population() {
+        let batch = Batch::new(&[]);
+        let mut scores = vec![1.0];
+        batch.scores(&EncodedEnv::new("01").unwrap(), &mut scores);
+        assert!(batch.is_empty());
+        assert!(scores.is_empty());
+    }
+}
@@ -0,0 +1,218 @@
+// A bounded fitness cache, so that flibs which survive from one generation
+// to the next aren't scored again.
+//
+// Entries are keyed by the flib's canonical chromosome (see
+// Flib::canonical_chromosome()), the environment and the horizon, so one
+// cache can safely be shared between runs with different settings, and
+// flibs that differ only in how their states are numbered, or in states
+// they can never reach, share an entry.  Once the cache is full, the
+// least recently used entry is evicted to make room.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+use crate::{Flib, Horizon};
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Key {
+    chromosome: String,
+    environment: String,
+    horizon: Horizon,
+}
+
+// How well the cache has been doing.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+}
+
+impl CacheStats {
+    pub fn lookups(&self) -> usize {
+        self.hits + self.misses
+    }
+
+    // Fraction of lookups that were answered from the cache.
+    pub fn hit_rate(&self) -> f64 {
+        match self.lookups() {
+            0 => 0.0,
+            lookups => (self.hits as f64) / (lookups as f64),
+        }
+    }
+}
+
+impl fmt::Display for CacheStats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} hits, {} misses ({:.1}% hit rate), {} evictions",
+            self.hits,
+            self.misses,
+            100.0 * self.hit_rate(),
+            self.evictions
+        )
+    }
+}
+
+#[derive(Debug)]
+pub struct FitnessCache {
+    capacity: usize,
+    // Each entry's score and when it was last used.
+    entries: HashMap<Key, (f32, u64)>,
+    // Keys by when they were last used, oldest first.
+    recency: BTreeMap<u64, Key>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+impl FitnessCache {
+    // A cache holding at most `capacity` scores.  A capacity of 0 disables
+    // caching: every lookup is a miss and nothing is stored.
+    pub fn new(capacity: usize) -> FitnessCache {
+        FitnessCache {
+            capacity,
+            entries: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.capacity > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    // Return the cached score of `flib`, or compute it with `score` and
+    // remember it.
+    pub fn score<F: FnOnce(&mut Flib) -> f32>(
+        &mut self,
+        flib: &mut Flib,
+        environment: &str,
+        horizon: Horizon,
+        score: F,
+    ) -> f32 {
+        if !self.is_enabled() {
+            self.stats.misses += 1;
+            return score(flib);
+        }
+        let key = Key {
+            chromosome: flib.canonical_chromosome(),
+            environment: environment.to_string(),
+            horizon,
+        };
+        self.clock += 1;
+        if let Some((value, used)) = self.entries.get_mut(&key) {
+            let key = self
+                .recency
+                .remove(used)
+                .expect("cache entry has no recency");
+            *used = self.clock;
+            self.recency.insert(self.clock, key);
+            self.stats.hits += 1;
+            return *value;
+        }
+
+        self.stats.misses += 1;
+        let value = score(flib);
+        if self.entries.len() == self.capacity {
+            let (_, oldest) = self.recency.pop_first().expect("full cache is empty");
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+        self.entries.insert(key.clone(), (value, self.clock));
+        self.recency.insert(self.clock, key);
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_from_chromosome;
+    use std::cell::Cell;
+
+    #[test]
+    fn test_hits_and_eviction() {
+        let mut cache = FitnessCache::new(2);
+        let calls = Cell::new(0);
+        let lookup = |cache: &mut FitnessCache, chromosome: &str| {
+            let mut flib = make_from_chromosome(chromosome.to_string());
+            cache.score(&mut flib, "011001", Horizon::default(), |flib| {
+                calls.set(calls.get() + 1);
+                flib.predict("011001")
+            })
+        };
+
+        let echo = lookup(&mut cache, "0A1A");
+        assert_eq!(lookup(&mut cache, "0A1A"), echo);
+        assert_eq!(calls.get(), 1);
+        // The same machine with an unreachable state shares the entry.
+        assert_eq!(lookup(&mut cache, "0A1A1B0B"), echo);
+        assert_eq!(calls.get(), 1);
+
+        lookup(&mut cache, "0A0A");
+        lookup(&mut cache, "0A1A");
+        // The cache is full, so this evicts 0A0A, the least recently used.
+        lookup(&mut cache, "1A1A");
+        assert_eq!(cache.len(), 2);
+        lookup(&mut cache, "0A1A");
+        assert_eq!(calls.get(), 3);
+        lookup(&mut cache, "0A0A");
+        assert_eq!(calls.get(), 4);
+
+        let stats = cache.stats();
+        assert_eq!(
+            stats,
+            CacheStats {
+                hits: 4,
+                misses: 4,
+                evictions: 2
+            }
+        );
+        assert_eq!(stats.hit_rate(), 0.5);
+    }
+
+    #[test]
+    fn test_key_includes_settings() {
+        let mut cache = FitnessCache::new(10);
+        let mut echo = make_from_chromosome(String::from("0A1A"));
+        cache.score(&mut echo, "0011", Horizon::Ahead(1), |_| 0.5);
+        cache.score(&mut echo, "0011", Horizon::Ahead(2), |_| 0.0);
+        cache.score(&mut echo, "0101", Horizon::Ahead(1), |_| 0.0);
+        assert_eq!(
+            cache.score(&mut echo, "0011", Horizon::Ahead(1), |_| 1.0),
+            0.5
+        );
+        assert_eq!(cache.stats().misses, 3);
+        assert_eq!(cache.stats().hits, 1);
+    }
+
+    #[test]
+    fn test_disabled() {
+        let mut cache = FitnessCache::new(0);
+        let mut echo = make_from_chromosome(String::from("0A1A"));
+        assert_eq!(
+            cache.score(&mut echo, "0011", Horizon::default(), |_| 0.5),
+            0.5
+        );
+        assert_eq!(
+            cache.score(&mut echo, "0011", Horizon::default(), |_| 0.25),
+            0.25
+        );
+        assert!(cache.is_empty());
+        assert_eq!(cache.stats().misses, 2);
+    }
+}
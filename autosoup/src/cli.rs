@@ -0,0 +1,402 @@
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+use crate::codegen::Language;
+use crate::formats::Format;
+use crate::local_search::Cooling;
+use crate::tune::Method;
+use crate::variants::Kind;
+use crate::{Crossover, Horizon, Replacement, Search, Selection};
+
+// Options for the simulate subcommand.  Every one of them is optional: a
+// flag given here overrides the same setting in the --config file, which
+// in turn overrides the built-in default.
+#[derive(Debug, Default, StructOpt)]
+pub struct SimulateArgs {
+    /// TOML or JSON file describing the run
+    #[structopt(parse(from_os_str), short, long)]
+    pub config: Option<PathBuf>,
+
+    /// Environment strings to predict, one run each
+    #[structopt(long, use_delimiter = true)]
+    pub environments: Option<Vec<String>>,
+
+    /// Number of flibs in the population
+    #[structopt(long)]
+    pub population: Option<usize>,
+
+    /// Number of states in each flib
+    #[structopt(long)]
+    pub states: Option<usize>,
+
+    /// Selection scheme: worst-best or tournament:SIZE
+    #[structopt(long)]
+    pub selection: Option<Selection>,
+
+    /// Crossover operator: one-point or uniform
+    #[structopt(long)]
+    pub crossover: Option<Crossover>,
+
+    /// Chance of a mutation in each generation, or for each offspring
+    /// when whole generations are bred
+    #[structopt(long)]
+    pub mutation_rate: Option<f32>,
+
+    /// Replacement scheme: steady-state, generational, comma:LAMBDA for
+    /// (mu,lambda) or plus:LAMBDA for (mu+lambda), where mu is the
+    /// population size
+    #[structopt(long)]
+    pub replacement: Option<Replacement>,
+
+    /// Search to run: genetic, or hill-climb or anneal to evolve a single
+    /// flib as a baseline
+    #[structopt(long)]
+    pub search: Option<Search>,
+
+    /// Starting temperature for simulated annealing
+    #[structopt(long)]
+    pub temperature: Option<f32>,
+
+    /// Cooling schedule for simulated annealing: exponential:RATE, linear
+    /// or logarithmic
+    #[structopt(long)]
+    pub cooling: Option<Cooling>,
+
+    /// Judge each output against the symbol STEPS ahead (ahead:STEPS) or
+    /// against a forecast of SIZE symbols (window:SIZE)
+    #[structopt(long)]
+    pub horizon: Option<Horizon>,
+
+    /// Stop once a flib scores at least this much
+    #[structopt(long)]
+    pub target_score: Option<f32>,
+
+    /// Give up after this many generations
+    #[structopt(long)]
+    pub max_generations: Option<usize>,
+
+    /// Seed for the random number generator
+    #[structopt(long)]
+    pub seed: Option<u64>,
+
+    /// Number of scores to keep in the fitness cache, for window horizons;
+    /// ahead horizons only rescore the flibs that changed
+    #[structopt(long)]
+    pub cache_size: Option<usize>,
+
+    /// Score every flib in every generation, without the fitness cache,
+    /// for window horizons
+    #[structopt(long)]
+    pub no_cache: bool,
+
+    /// Don't print the population after every generation
+    #[structopt(short, long)]
+    pub quiet: bool,
+
+    /// Write per-generation statistics to this CSV file
+    #[structopt(parse(from_os_str), long)]
+    pub stats_csv: Option<PathBuf>,
+
+    /// Write the outcome of each run to this JSON file
+    #[structopt(parse(from_os_str), long)]
+    pub result_json: Option<PathBuf>,
+
+    /// Test each run's best flib against this many random flibs
+    #[structopt(long)]
+    pub significance: Option<usize>,
+
+    /// Write the ancestry of each run's best flib to this DOT file
+    #[structopt(parse(from_os_str), long)]
+    pub lineage_dot: Option<PathBuf>,
+
+    /// Write the ancestry of each run's best flib to this JSON file
+    #[structopt(parse(from_os_str), long)]
+    pub lineage_json: Option<PathBuf>,
+}
+
+#[derive(Debug, StructOpt)]
+pub enum Action {
+    /// Evolve flibs that predict a fixed environment (the default)
+    Simulate(SimulateArgs),
+    /// Run the simulation with a live terminal dashboard
+    Dashboard {
+        #[structopt(flatten)]
+        args: SimulateArgs,
+
+        /// File that the 'c' key saves a checkpoint to
+        #[structopt(parse(from_os_str), long, default_value = "autosoup-checkpoint.json")]
+        checkpoint: PathBuf,
+    },
+    /// Run every combination of settings with many seeds, in parallel, and
+    /// summarize the results
+    Experiment {
+        /// Environment strings to try
+        #[structopt(long, default_value = "011001", use_delimiter = true)]
+        environments: Vec<String>,
+
+        /// Population sizes to try
+        #[structopt(long, default_value = "10", use_delimiter = true)]
+        populations: Vec<usize>,
+
+        /// Selection schemes to try: worst-best or tournament:SIZE
+        #[structopt(long, default_value = "worst-best", use_delimiter = true)]
+        selections: Vec<Selection>,
+
+        /// Crossover operators to try: one-point or uniform
+        #[structopt(long, default_value = "one-point", use_delimiter = true)]
+        crossovers: Vec<Crossover>,
+
+        /// Per-generation mutation probabilities to try
+        #[structopt(long, default_value = "1.0", use_delimiter = true)]
+        mutation_rates: Vec<f32>,
+
+        /// Number of seeds to run for each combination
+        #[structopt(long, default_value = "20")]
+        seeds: u64,
+
+        /// Count a run as failed after this many generations
+        #[structopt(long, default_value = "10000")]
+        max_generations: usize,
+
+        /// Write one line per run to this CSV file
+        #[structopt(parse(from_os_str), long)]
+        csv: Option<PathBuf>,
+
+        /// Write the summary table to this CSV file
+        #[structopt(parse(from_os_str), long)]
+        summary_csv: Option<PathBuf>,
+    },
+    /// Co-evolve flibs against a population of environments that evolve to
+    /// be hard to predict
+    Coevolve {
+        /// Number of generations to run
+        #[structopt(long, default_value = "500")]
+        generations: usize,
+
+        /// Number of flibs in the predictor population
+        #[structopt(long, default_value = "10")]
+        flibs: usize,
+
+        /// Number of strings in the environment population
+        #[structopt(long, default_value = "10")]
+        environments: usize,
+
+        /// Length of each environment string
+        #[structopt(long, default_value = "6")]
+        env_length: usize,
+
+        /// Print a progress line every N generations
+        #[structopt(long, default_value = "10")]
+        report_every: usize,
+
+        /// Seed for the random number generator
+        #[structopt(long)]
+        seed: Option<u64>,
+    },
+    /// Trade off prediction accuracy against machine size, printing the
+    /// Pareto front of the final population
+    Pareto {
+        /// Environment string to predict
+        #[structopt(long, default_value = "011001")]
+        environment: String,
+
+        /// Number of generations to run
+        #[structopt(long, default_value = "200")]
+        generations: usize,
+
+        /// Number of flibs in the population
+        #[structopt(long, default_value = "40")]
+        population: usize,
+
+        /// Number of states in each flib; the reachable count may be smaller
+        #[structopt(long, default_value = "8")]
+        states: usize,
+
+        /// Also write the front as JSON to this file
+        #[structopt(parse(from_os_str), long)]
+        json: Option<PathBuf>,
+
+        /// Seed for the random number generator
+        #[structopt(long)]
+        seed: Option<u64>,
+    },
+    /// Evolve a flib that turns the inputs in a trace file into the
+    /// matching outputs
+    Induce {
+        /// File of input and expected output sequences, one pair per line
+        #[structopt(parse(from_os_str))]
+        traces: PathBuf,
+
+        /// Number of states in each flib
+        #[structopt(long, default_value = "4")]
+        states: usize,
+
+        /// Number of flibs in the population
+        #[structopt(long, default_value = "20")]
+        population: usize,
+
+        /// Selection scheme: worst-best or tournament:SIZE
+        #[structopt(long, default_value = "worst-best")]
+        selection: Selection,
+
+        /// Crossover operator: one-point or uniform
+        #[structopt(long, default_value = "one-point")]
+        crossover: Crossover,
+
+        /// Stop once a flib's accuracy reaches this much
+        #[structopt(long, default_value = "1.0")]
+        target_score: f32,
+
+        /// Give up after this many generations
+        #[structopt(long, default_value = "100000")]
+        max_generations: usize,
+
+        /// Seed for the random number generator
+        #[structopt(long)]
+        seed: Option<u64>,
+    },
+    /// Print a flib as a state-machine function in Rust, C or Python
+    Codegen {
+        /// Chromosome of the flib, such as 0B1B1A0A
+        chromosome: String,
+
+        /// Language to generate: rust, c or python
+        #[structopt(long, default_value = "rust")]
+        language: Language,
+
+        /// Name of the generated function
+        #[structopt(long, default_value = "flib_step")]
+        name: String,
+    },
+    /// Print a flib in another automata format
+    Export {
+        /// Chromosome of the flib, such as 0B1B1A0A
+        chromosome: String,
+
+        /// Format to write: json, hoa or kiss2
+        #[structopt(long, default_value = "json")]
+        format: Format,
+    },
+    /// Read a machine in another automata format and print its chromosome
+    Import {
+        /// File to read
+        #[structopt(parse(from_os_str))]
+        file: PathBuf,
+
+        /// Format of the file: json, hoa or kiss2; by default, guessed from
+        /// the file's extension
+        #[structopt(long)]
+        format: Option<Format>,
+    },
+    /// Test whether a flib predicts an environment better than chance
+    Significance {
+        /// Chromosome of the flib, such as 0B1B1A0A
+        chromosome: String,
+
+        /// Environment string to predict
+        #[structopt(long, default_value = "011001")]
+        environment: String,
+
+        /// Judge outputs against ahead:STEPS or window:SIZE
+        #[structopt(long, default_value = "ahead:1")]
+        horizon: Horizon,
+
+        /// Number of random flibs to sample
+        #[structopt(long, default_value = "10000")]
+        samples: usize,
+
+        /// Seed for the random number generator
+        #[structopt(long)]
+        seed: Option<u64>,
+    },
+    /// Compare how quickly different genome kinds learn to predict the
+    /// same environments
+    Compare {
+        /// Genome kinds: mealy, moore, probabilistic:expected or
+        /// probabilistic:log-likelihood
+        #[structopt(
+            long,
+            default_value = "mealy,moore,probabilistic:expected,probabilistic:log-likelihood",
+            use_delimiter = true
+        )]
+        kinds: Vec<Kind>,
+
+        /// Environment strings to try
+        #[structopt(long, default_value = "011001", use_delimiter = true)]
+        environments: Vec<String>,
+
+        /// Number of individuals in the population
+        #[structopt(long, default_value = "10")]
+        population: usize,
+
+        /// Number of states in each machine; defaults to half the
+        /// environment's length, plus one
+        #[structopt(long)]
+        states: Option<usize>,
+
+        /// Number of seeds to run for each kind and environment
+        #[structopt(long, default_value = "20")]
+        seeds: u64,
+
+        /// Count a run as failed after this many generations
+        #[structopt(long, default_value = "10000")]
+        max_generations: usize,
+    },
+    /// Search for the population size and operators that find perfect
+    /// predictors in the fewest generations, and print them as a config
+    /// file for simulate
+    Tune {
+        /// Environment strings to tune for
+        #[structopt(long, default_value = "011001", use_delimiter = true)]
+        environments: Vec<String>,
+
+        /// Search method: random or halving (successive halving)
+        #[structopt(long, default_value = "halving")]
+        method: Method,
+
+        /// Number of random settings to try
+        #[structopt(long, default_value = "16")]
+        candidates: usize,
+
+        /// Seeds per environment for each setting; with halving, the
+        /// number in the first round
+        #[structopt(long, default_value = "4")]
+        seeds: u64,
+
+        /// Count a run as failed after this many generations
+        #[structopt(long, default_value = "10000")]
+        max_generations: usize,
+
+        /// Seed for drawing the settings to try
+        #[structopt(long)]
+        seed: Option<u64>,
+
+        /// Write the config to this file rather than printing it
+        #[structopt(parse(from_os_str), long)]
+        output: Option<PathBuf>,
+    },
+    /// Serve a local HTTP/JSON API for submitting and monitoring runs
+    Serve {
+        /// Address to listen on
+        #[structopt(long, default_value = "127.0.0.1:8080")]
+        address: String,
+
+        /// Number of jobs to run at once
+        #[structopt(long, default_value = "2")]
+        workers: usize,
+
+        /// Number of jobs that can wait for a worker
+        #[structopt(long, default_value = "16")]
+        queue: usize,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+    name = "autosoup",
+    about = "Evolve finite-state machines that predict their environment"
+)]
+pub struct CommandLineArgs {
+    #[structopt(subcommand)]
+    pub action: Option<Action>,
+}
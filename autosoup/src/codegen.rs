@@ -0,0 +1,328 @@
+// Code generation: turn a flib into a self-contained state-machine
+// function that can be pasted into another program.
+//
+// Every language gets the same step function.  It takes the current state,
+// starting from 0, and one input character, '0' or '1'.  It returns the
+// output character and moves to the next state, exactly like
+// Flib::transition().  The states are numbered as in the chromosome,
+// so state 1 is 'B'.
+
+use std::fmt::Write;
+use std::str::FromStr;
+
+use crate::Flib;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Language {
+    Rust,
+    C,
+    Python,
+}
+
+impl FromStr for Language {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Language, String> {
+        match s {
+            "rust" => Ok(Language::Rust),
+            "c" => Ok(Language::C),
+            "python" => Ok(Language::Python),
+            _ => Err(format!(
+                "unknown language '{}'; expected rust, c or python",
+                s
+            )),
+        }
+    }
+}
+
+// Words that can't name a function in each language.  Rust's include the
+// ones reserved for future use.
+const RUST_KEYWORDS: &[&str] = &[
+    "_", "abstract", "as", "async", "await", "become", "box", "break", "const", "continue",
+    "crate", "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if",
+    "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+    "ref", "return", "self", "Self", "static", "struct", "super", "trait", "true", "try", "type",
+    "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield",
+];
+const C_KEYWORDS: &[&str] = &[
+    "_Alignas",
+    "_Alignof",
+    "_Atomic",
+    "_Bool",
+    "_Complex",
+    "_Generic",
+    "_Imaginary",
+    "_Noreturn",
+    "_Static_assert",
+    "_Thread_local",
+    "alignas",
+    "alignof",
+    "auto",
+    "bool",
+    "break",
+    "case",
+    "char",
+    "const",
+    "constexpr",
+    "continue",
+    "default",
+    "do",
+    "double",
+    "else",
+    "enum",
+    "extern",
+    "false",
+    "float",
+    "for",
+    "goto",
+    "if",
+    "inline",
+    "int",
+    "long",
+    "nullptr",
+    "register",
+    "restrict",
+    "return",
+    "short",
+    "signed",
+    "sizeof",
+    "static",
+    "static_assert",
+    "struct",
+    "switch",
+    "thread_local",
+    "true",
+    "typedef",
+    "typeof",
+    "typeof_unqual",
+    "union",
+    "unsigned",
+    "void",
+    "volatile",
+    "while",
+];
+const PYTHON_KEYWORDS: &[&str] = &[
+    "False", "None", "True", "and", "as", "assert", "async", "await", "break", "class", "continue",
+    "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import",
+    "in", "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while",
+    "with", "yield",
+];
+
+// Generate a step function called `name` for `flib`.  The name is pasted
+// into the source as it is, so it has to be a plain identifier that isn't
+// one of the language's keywords.
+pub fn generate(flib: &Flib, language: Language, name: &str) -> Result<String, String> {
+    check_name(name, language)?;
+    Ok(match language {
+        Language::Rust => generate_rust(flib, name),
+        Language::C => generate_c(flib, name),
+        Language::Python => generate_python(flib, name),
+    })
+}
+
+fn check_name(name: &str, language: Language) -> Result<(), String> {
+    let mut chars = name.chars();
+    let identifier = chars
+        .next()
+        .is_some_and(|ch| ch.is_ascii_alphabetic() || ch == '_')
+        && chars.all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+    if !identifier {
+        return Err(format!(
+            "function name '{}' must be letters, digits and underscores, not starting \
+             with a digit",
+            name
+        ));
+    }
+    let (keywords, language_name) = match language {
+        Language::Rust => (RUST_KEYWORDS, "Rust"),
+        Language::C => (C_KEYWORDS, "C"),
+        Language::Python => (PYTHON_KEYWORDS, "Python"),
+    };
+    if keywords.contains(&name) {
+        return Err(format!(
+            "function name '{}' is a keyword in {}",
+            name, language_name
+        ));
+    }
+    Ok(())
+}
+
+fn generate_rust(flib: &Flib, name: &str) -> String {
+    let mut code = format!("// Generated by autosoup from {}.\n", flib.as_chromosome());
+    code.push_str(
+        "// Start with `state` at 0 and call once per input character, '0' or '1';\n\
+         // returns the output character.\n",
+    );
+    writeln!(
+        code,
+        "pub fn {}(state: &mut usize, input: char) -> char {{",
+        name
+    )
+    .unwrap();
+    code.push_str("    let (output, next) = match (*state, input) {\n");
+    for (state, transitions) in flib.states.iter().enumerate() {
+        for (input, &(output, dest)) in transitions.iter().enumerate() {
+            writeln!(
+                code,
+                "        ({}, '{}') => ('{}', {}),",
+                state, input, output, dest
+            )
+            .unwrap();
+        }
+    }
+    code.push_str("        _ => panic!(\"invalid state or input\"),\n");
+    code.push_str("    };\n    *state = next;\n    output\n}\n");
+    code
+}
+
+fn generate_c(flib: &Flib, name: &str) -> String {
+    let mut code = format!("/* Generated by autosoup from {}.\n", flib.as_chromosome());
+    code.push_str(
+        " * Start with *state at 0 and call once per input character, '0' or '1';\n\
+         * returns the output character, or -1 for an invalid state or input. */\n",
+    );
+    writeln!(code, "int {}(int *state, char input)\n{{", name).unwrap();
+    code.push_str("    int bit = input - '0';\n");
+    code.push_str("    if (bit != 0 && bit != 1)\n        return -1;\n");
+    code.push_str("    switch (*state * 2 + bit) {\n");
+    for (state, transitions) in flib.states.iter().enumerate() {
+        for (input, &(output, dest)) in transitions.iter().enumerate() {
+            writeln!(
+                code,
+                "    case {}: *state = {}; return '{}';",
+                state * 2 + input,
+                dest,
+                output
+            )
+            .unwrap();
+        }
+    }
+    code.push_str("    default: return -1;\n    }\n}\n");
+    code
+}
+
+fn generate_python(flib: &Flib, name: &str) -> String {
+    let mut code = format!("# Generated by autosoup from {}.\n", flib.as_chromosome());
+    code.push_str("# Maps (state, input) to (output, next state).\n");
+    let table = name.to_uppercase() + "_TABLE";
+    writeln!(code, "{} = {{", table).unwrap();
+    for (state, transitions) in flib.states.iter().enumerate() {
+        for (input, &(output, dest)) in transitions.iter().enumerate() {
+            writeln!(
+                code,
+                "    ({}, '{}'): ('{}', {}),",
+                state, input, output, dest
+            )
+            .unwrap();
+        }
+    }
+    code.push_str("}\n\n\n");
+    writeln!(code, "def {}(state, input):", name).unwrap();
+    code.push_str(
+        "    \"\"\"Start with state 0 and call once per input character, '0' or '1'.\n\n    \
+         Returns the output character and the next state.\n    \"\"\"\n",
+    );
+    writeln!(code, "    return {}[(state, input)]", table).unwrap();
+    code
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_from_chromosome;
+    use rand::{Rng, SeedableRng, StdRng};
+    use std::fs;
+    use std::process::Command;
+
+    #[test]
+    fn test_generated_rust() {
+        let flib = make_from_chromosome(String::from("0B1A"));
+        assert_eq!(
+            generate(&flib, Language::Rust, "step").unwrap(),
+            "// Generated by autosoup from 0B1A.\n\
+             // Start with `state` at 0 and call once per input character, '0' or '1';\n\
+             // returns the output character.\n\
+             pub fn step(state: &mut usize, input: char) -> char {\n    \
+                 let (output, next) = match (*state, input) {\n        \
+                     (0, '0') => ('0', 1),\n        \
+                     (0, '1') => ('1', 0),\n        \
+                     _ => panic!(\"invalid state or input\"),\n    \
+                 };\n    \
+                 *state = next;\n    \
+                 output\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn test_generated_c_and_python() {
+        let flib = make_from_chromosome(String::from("0B1A"));
+        let c = generate(&flib, Language::C, "step").unwrap();
+        assert!(c.contains("int step(int *state, char input)"));
+        assert!(c.contains("    case 1: *state = 0; return '1';\n"));
+        let python = generate(&flib, Language::Python, "step").unwrap();
+        assert!(python.contains("    (0, '0'): ('0', 1),\n"));
+        assert!(python.contains("def step(state, input):"));
+    }
+
+    #[test]
+    fn test_bad_names() {
+        let flib = make_from_chromosome(String::from("0B1A"));
+        for &language in &[Language::Rust, Language::C, Language::Python] {
+            for name in &["", "2step", "f(); system(\"ls\")", "step-two", "näme"] {
+                assert!(generate(&flib, language, name).is_err(), "{:?}", name);
+            }
+            assert!(generate(&flib, language, "_step2").is_ok());
+        }
+        // A keyword in one language may be fine in another.
+        assert!(generate(&flib, Language::Rust, "fn").is_err());
+        assert!(generate(&flib, Language::C, "fn").is_ok());
+        assert!(generate(&flib, Language::C, "int").is_err());
+        assert!(generate(&flib, Language::Python, "lambda").is_err());
+        assert!(generate(&flib, Language::Rust, "lambda").is_ok());
+    }
+
+    // Compile the generated Rust, together with a main() that runs it over
+    // the inputs given on its command line, and compare what it prints with
+    // Flib::transduce() on random flibs and inputs.
+    #[test]
+    fn test_compiled_rust_matches_transition() {
+        let dir = std::env::temp_dir().join(format!("autosoup-codegen-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let rustc = std::env::var("RUSTC").unwrap_or_else(|_| String::from("rustc"));
+        let mut rng = StdRng::from_seed(&[36][..]);
+
+        for i in 0..3 {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(rng.gen_range(1, 8), &mut rng);
+            let source = dir.join(format!("flib{}.rs", i));
+            let binary = dir.join(format!("flib{}", i));
+            let program = generate(&flib, Language::Rust, "step").unwrap()
+                + "\nfn main() {\n    \
+                   let mut state = 0;\n    \
+                   let input = std::env::args().nth(1).unwrap();\n    \
+                   let output: String = input.chars().map(|ch| step(&mut state, ch)).collect();\n    \
+                   print!(\"{}\", output);\n\
+                   }\n";
+            fs::write(&source, program).unwrap();
+            let status = Command::new(&rustc)
+                .arg(&source)
+                .arg("-o")
+                .arg(&binary)
+                .status()
+                .expect("failed to run rustc");
+            assert!(status.success(), "generated code didn't compile");
+
+            for _ in 0..5 {
+                let length = rng.gen_range(1, 50);
+                let input: String = (0..length)
+                    .map(|_| if rng.gen() { '1' } else { '0' })
+                    .collect();
+                let output = Command::new(&binary).arg(&input).output().unwrap();
+                let printed = String::from_utf8(output.stdout).unwrap();
+                assert_eq!(printed, flib.transduce(&input));
+            }
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
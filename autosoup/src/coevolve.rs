@@ -0,0 +1,281 @@
+// Competitive co-evolution of flibs and their environments.
+//
+// Alongside the population of flibs, a second population of binary
+// environment strings evolves.  An environment's fitness is how badly the
+// current flibs predict it, and a flib's fitness is its average score over
+// all of the current environments, so each population is the other's
+// moving target.
+
+use rand::Rng;
+
+use crate::batch::Batch;
+use crate::compiled::EncodedEnv;
+use crate::{diversity, find_minmax, make_from_chromosome, mutate, random_combine, Flib};
+
+pub struct CoevolutionParams {
+    pub generations: usize,
+    pub flib_population: usize,
+    pub env_population: usize,
+    pub env_length: usize,
+    pub num_states: usize,
+}
+
+// Snapshot of the arms race after one generation.
+#[derive(Debug)]
+pub struct Round {
+    pub generation: usize,
+    // Average score over every flib/environment pairing.
+    pub mean_accuracy: f32,
+    // Flib with the best average score.
+    pub best_flib: String,
+    pub best_flib_score: f32,
+    // Environment the flibs predict worst, and their average score on it.
+    pub hardest_env: String,
+    pub hardest_env_score: f32,
+    // Mean pairwise Hamming distance between environments; 0.0 means they're
+    // all identical.
+    pub env_diversity: f32,
+}
+
+fn random_environment<R: Rng>(length: usize, rng: &mut R) -> String {
+    (0..length)
+        .map(|_| (b'0' + rng.gen_range(0, 2)) as char)
+        .collect()
+}
+
+// Flip the bit at `position` in an environment string.
+fn flip_bit_at(environment: &str, position: usize) -> String {
+    environment
+        .chars()
+        .enumerate()
+        .map(|(i, ch)| match (i == position, ch) {
+            (false, _) => ch,
+            (true, '0') => '1',
+            (true, '1') => '0',
+            _ => panic!("unexpected character in environment"),
+        })
+        .collect()
+}
+
+fn flip_bit<R: Rng>(environment: &str, rng: &mut R) -> String {
+    let position = rng.gen_range(0, environment.len());
+    flip_bit_at(environment, position)
+}
+
+// Score every flib against every environment.  Entry [f][e] is flib f's
+// score on environment e.  The flibs are compiled once and run as a batch
+// against each environment in turn.
+fn score_matrix(flibs: &[Flib], environments: &[String]) -> Vec<Vec<f32>> {
+    let batch = Batch::new(flibs);
+    let mut matrix = vec![Vec::with_capacity(environments.len()); flibs.len()];
+    let mut scores = vec![];
+    for environment in environments {
+        let env = EncodedEnv::new(environment).expect("environments are binary strings");
+        batch.scores(&env, &mut scores);
+        for (row, &score) in matrix.iter_mut().zip(&scores) {
+            row.push(score);
+        }
+    }
+    matrix
+}
+
+fn flib_fitness(matrix: &[Vec<f32>]) -> Vec<f32> {
+    matrix
+        .iter()
+        .map(|row| row.iter().sum::<f32>() / (row.len() as f32))
+        .collect()
+}
+
+// An environment is fit when the flibs do badly on it, so its fitness is
+// one minus the flibs' average score.
+fn env_fitness(matrix: &[Vec<f32>]) -> Vec<f32> {
+    let num_envs = matrix[0].len();
+    (0..num_envs)
+        .map(|e| {
+            let total: f32 = matrix.iter().map(|row| row[e]).sum();
+            1.0 - total / (matrix.len() as f32)
+        })
+        .collect()
+}
+
+// Cross the best and worst-scoring chromosomes, replacing the worst, and then
+// mutate one other member at random.  This is the same steady-state scheme
+// that simulate() uses.
+fn breed<R: Rng>(
+    chromosomes: &mut [String],
+    fitness: &[f32],
+    rng: &mut R,
+    mutate: fn(&str, &mut R) -> String,
+) {
+    let (min_index, max_index) = find_minmax(fitness);
+    chromosomes[min_index] = random_combine(&chromosomes[min_index], &chromosomes[max_index], rng);
+
+    let random_index = rng.gen_range(0, chromosomes.len());
+    if random_index != max_index && random_index != min_index {
+        chromosomes[random_index] = mutate(&chromosomes[random_index], rng);
+    }
+}
+
+// Run the arms race for `params.generations` generations, calling
+// `on_round` after each one is scored.  Returns the full history.
+pub fn coevolve<R: Rng, F: FnMut(&Round)>(
+    params: &CoevolutionParams,
+    rng: &mut R,
+    mut on_round: F,
+) -> Vec<Round> {
+    assert!(
+        params.flib_population > 0,
+        "there must be at least one flib"
+    );
+    assert!(
+        params.env_population > 0,
+        "there must be at least one environment"
+    );
+    assert!(params.env_length > 0, "environments must not be empty");
+
+    let mut flib_chromosomes: Vec<String> = (0..params.flib_population)
+        .map(|_| {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(params.num_states, rng);
+            flib.as_chromosome()
+        })
+        .collect();
+    let mut environments: Vec<String> = (0..params.env_population)
+        .map(|_| random_environment(params.env_length, rng))
+        .collect();
+
+    let mut history = vec![];
+    for generation in 0..=params.generations {
+        let flibs: Vec<Flib> = flib_chromosomes
+            .iter()
+            .map(|c| make_from_chromosome(c.clone()))
+            .collect();
+        let matrix = score_matrix(&flibs, &environments);
+        let flib_scores = flib_fitness(&matrix);
+        let env_scores = env_fitness(&matrix);
+
+        let (_, best_flib) = find_minmax(&flib_scores);
+        let (_, hardest_env) = find_minmax(&env_scores);
+        let round = Round {
+            generation,
+            mean_accuracy: flib_scores.iter().sum::<f32>() / (flib_scores.len() as f32),
+            best_flib: flib_chromosomes[best_flib].clone(),
+            best_flib_score: flib_scores[best_flib],
+            hardest_env: environments[hardest_env].clone(),
+            hardest_env_score: 1.0 - env_scores[hardest_env],
+            env_diversity: diversity(&environments),
+        };
+        on_round(&round);
+        history.push(round);
+
+        if generation < params.generations {
+            breed(&mut flib_chromosomes, &flib_scores, rng, mutate);
+            breed(&mut environments, &env_scores, rng, flip_bit);
+        }
+    }
+
+    history
+}
+
+// Run a co-evolution and print how the arms race develops.
+pub fn run<R: Rng>(params: &CoevolutionParams, report_every: usize, rng: &mut R) {
+    println!(
+        "{:>6}  {:>6}  {:>6}  {:<w$}  {:>6}  {:>9}",
+        "gen",
+        "mean",
+        "best",
+        "hardest env",
+        "score",
+        "diversity",
+        w = params.env_length.max(11),
+    );
+    let history = coevolve(params, rng, |round| {
+        if round.generation % report_every.max(1) == 0 || round.generation == params.generations {
+            println!(
+                "{:>6}  {:>6.3}  {:>6.3}  {:<w$}  {:>6.3}  {:>9.3}",
+                round.generation,
+                round.mean_accuracy,
+                round.best_flib_score,
+                round.hardest_env,
+                round.hardest_env_score,
+                round.env_diversity,
+                w = params.env_length.max(11),
+            );
+        }
+    });
+
+    // Summarize the trend by averaging the flibs' accuracy over each
+    // quarter of the run.
+    println!();
+    println!("Mean flib accuracy by quarter of the run:");
+    let quarter = history.len().div_ceil(4);
+    for (i, chunk) in history.chunks(quarter).enumerate() {
+        let mean = chunk.iter().map(|r| r.mean_accuracy).sum::<f32>() / (chunk.len() as f32);
+        println!(
+            "  Q{} (generations {}-{}): {:.3}",
+            i + 1,
+            chunk[0].generation,
+            chunk[chunk.len() - 1].generation,
+            mean
+        );
+    }
+
+    let last = &history[history.len() - 1];
+    println!();
+    println!(
+        "Best flib: {} ({:.3})",
+        last.best_flib, last.best_flib_score
+    );
+    println!(
+        "Hardest environment: {} ({:.3})",
+        last.hardest_env, last.hardest_env_score
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn test_flip_bit() {
+        assert_eq!(flip_bit_at("0110", 0), "1110");
+        assert_eq!(flip_bit_at("0110", 2), "0100");
+        assert_eq!(flip_bit("0", &mut rand::thread_rng()), "1");
+    }
+
+    #[test]
+    fn test_fitness_is_adversarial() {
+        // Two flibs, two environments: the environment the flibs predict
+        // worst should be the fittest one.
+        let matrix = vec![vec![1.0, 0.5], vec![0.5, 0.0]];
+        assert_eq!(flib_fitness(&matrix), vec![0.75, 0.25]);
+        assert_eq!(env_fitness(&matrix), vec![0.25, 0.75]);
+    }
+
+    #[test]
+    fn test_coevolve_runs() {
+        let params = CoevolutionParams {
+            generations: 20,
+            flib_population: 4,
+            env_population: 3,
+            env_length: 5,
+            num_states: 3,
+        };
+        let run = |seed| {
+            let mut rng = StdRng::from_seed(&[seed][..]);
+            coevolve(&params, &mut rng, |_| {})
+        };
+        let history = run(1);
+        assert_eq!(history.len(), 21);
+        // The same seed gives the same arms race.
+        let again = run(1);
+        assert_eq!(history[20].best_flib, again[20].best_flib);
+        assert_eq!(history[20].hardest_env, again[20].hardest_env);
+        for round in &history {
+            assert_eq!(round.hardest_env.len(), 5);
+            assert_eq!(round.best_flib.len(), 12);
+            assert!(round.mean_accuracy >= 0.0 && round.mean_accuracy <= 1.0);
+        }
+    }
+}
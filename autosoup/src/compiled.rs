@@ -0,0 +1,370 @@
+// Compiled flibs for high-throughput scoring.
+//
+// Flib::predict() builds two new strings on every call and walks a
+// Vec<Vec<(char, usize)>>.  That's fine for looking at one flib, but
+// scoring a whole population every generation is dominated by the
+// overhead.  A Scorer instead packs the environment into bits once and
+// compiles the population into one flat table of byte-sized transitions,
+// in a buffer that's reused from one call to the next, so scoring doesn't
+// allocate at all.
+//
+// Running a single flib is a chain of dependent table lookups, so the
+// Scorer steps several flibs through the environment together, which lets
+// the CPU overlap their lookups.  Compiling is slower than scoring, because
+// it has to chase the pointers in Flib's nested vectors; since a
+// generation only changes a few flibs, the Scorer keeps the compiled table
+// between generations and recompiles just the flibs that changed.
+
+use crate::{Flib, Horizon, MAX_STATES};
+
+// Output code for anything other than '0' or '1', which can never match
+// the environment.
+const NO_MATCH: u8 = 2;
+
+// Number of flibs stepped through the environment together.
+const LANES: usize = 8;
+
+// An environment prepared for scoring.  predict() feeds a flib two copies
+// of the environment and compares each output with a later symbol, so this
+// holds the input and the expected output of every step, as bit `i % 64`
+// of word `i / 64`.
+#[derive(Debug)]
+pub struct EncodedEnv {
+    inputs: Vec<u64>,
+    expected: Vec<u64>,
+    num_steps: usize,
+}
+
+fn pack(bits: impl Iterator<Item = u8>) -> Vec<u64> {
+    let mut words = vec![];
+    for (i, bit) in bits.enumerate() {
+        if i % 64 == 0 {
+            words.push(0);
+        }
+        words[i / 64] |= u64::from(bit) << (i % 64);
+    }
+    words
+}
+
+impl EncodedEnv {
+    // Returns None unless the environment is a non-empty string of 0s and
+    // 1s, the only inputs a flib accepts.
+    pub fn new(environment: &str) -> Option<EncodedEnv> {
+        EncodedEnv::ahead(environment, 1)
+    }
+
+    // Expect each output to match the symbol `k` steps ahead, as with
+    // Horizon::Ahead(k).
+    pub fn ahead(environment: &str, k: usize) -> Option<EncodedEnv> {
+        let bits: Vec<u8> = environment
+            .chars()
+            .map(|ch| match ch {
+                '0' => Some(0),
+                '1' => Some(1),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        if bits.is_empty() {
+            return None;
+        }
+
+        let n = bits.len();
+        Some(EncodedEnv {
+            inputs: pack((0..2 * n).map(|i| bits[i % n])),
+            expected: pack((0..2 * n).map(|i| bits[(i + k) % n])),
+            num_steps: 2 * n,
+        })
+    }
+
+    // The (input, expected output) pair for each step, each a 0 or 1.
+    pub fn steps(&self) -> impl Iterator<Item = (u8, u8)> + '_ {
+        let bit = |words: &[u64], i: usize| ((words[i / 64] >> (i % 64)) & 1) as u8;
+        (0..self.num_steps).map(move |i| (bit(&self.inputs, i), bit(&self.expected, i)))
+    }
+
+    // Number of predictions made, which is twice the environment's length.
+    pub fn num_steps(&self) -> usize {
+        self.num_steps
+    }
+}
+
+// Compile `flib` into `table`, starting at index `start`.  A state's two
+// transitions sit next to each other, input 0 first, and each entry packs
+// the destination state's offset from `start` above a two-bit output code.
+// With at most MAX_STATES states the offset fits in the byte's other six
+// bits.  Stepping a flib is then a single lookup at `start + offset +
+// input`.
+fn compile_at(flib: &Flib, table: &mut [u8], start: usize) {
+    let num_states = flib.states.len();
+    assert!(num_states <= MAX_STATES, "too many states to compile");
+    let entries = flib.states.iter().flatten();
+    for (slot, &(output, dest)) in table[start..start + 2 * num_states].iter_mut().zip(entries) {
+        assert!(dest < num_states, "transition to a missing state");
+        let code = match output {
+            '0' => 0,
+            '1' => 1,
+            _ => NO_MATCH,
+        };
+        *slot = ((2 * dest) as u8) << 2 | code;
+    }
+}
+
+// Append the transition table for `flib` to `table`, returning the index
+// of its starting state.
+pub fn compile_into(flib: &Flib, table: &mut Vec<u8>) -> u32 {
+    let start = table.len();
+    table.resize(start + 2 * flib.states.len(), 0);
+    compile_at(flib, table, start);
+    start as u32
+}
+
+// Run up to LANES compiled flibs, starting at the table indexes in
+// `starts`, over the environment, returning each one's number of correct
+// predictions.
+fn count_matches(table: &[u8], starts: &[u32], env: &EncodedEnv) -> [u32; LANES] {
+    // Lanes past the end of `starts` just rerun the first flib.
+    let mut start = [starts[0] as usize; LANES];
+    for (lane, &s) in starts.iter().enumerate() {
+        start[lane] = s as usize;
+    }
+    let mut offset = [0u8; LANES];
+    let mut matches = [0; LANES];
+
+    for (word, (&inputs, &expected)) in env.inputs.iter().zip(&env.expected).enumerate() {
+        for bit in 0..(env.num_steps - 64 * word).min(64) {
+            let input = ((inputs >> bit) & 1) as u8;
+            let expected = ((expected >> bit) & 1) as u8;
+            for lane in 0..LANES {
+                let entry = table[(start[lane] + usize::from(input)) + usize::from(offset[lane])];
+                matches[lane] += ((entry & 3) == expected) as u32;
+                offset[lane] = entry >> 2;
+            }
+        }
+    }
+    matches
+}
+
+// Scores flibs against one environment, reusing its buffers for every
+// call.
+#[derive(Debug)]
+pub struct Scorer {
+    env: EncodedEnv,
+    table: Vec<u8>,
+    // Table index and state count of each compiled flib.
+    starts: Vec<u32>,
+    sizes: Vec<usize>,
+}
+
+impl Scorer {
+    pub fn new(environment: &str) -> Option<Scorer> {
+        Scorer::with_horizon(environment, Horizon::Ahead(1))
+    }
+
+    // Returns None for windowed horizons, which feed a flib its own
+    // predictions and so can't be compiled this way.
+    pub fn with_horizon(environment: &str, horizon: Horizon) -> Option<Scorer> {
+        let k = match horizon {
+            Horizon::Ahead(k) => k,
+            Horizon::Window(_) => return None,
+        };
+        Some(Scorer {
+            env: EncodedEnv::ahead(environment, k)?,
+            table: vec![],
+            starts: vec![],
+            sizes: vec![],
+        })
+    }
+
+    // Score a single flib.  Gives exactly the same result as
+    // Flib::predict().  This replaces any compiled population.
+    pub fn score(&mut self, flib: &Flib) -> f32 {
+        self.compile(std::slice::from_ref(flib));
+        let matches = count_matches(&self.table, &self.starts, &self.env);
+        (matches[0] as f32) / (self.env.num_steps() as f32)
+    }
+
+    // Compile a population, replacing whatever was compiled before.
+    pub fn compile(&mut self, population: &[Flib]) {
+        self.table.clear();
+        self.starts.clear();
+        self.sizes.clear();
+        for flib in population {
+            self.starts.push(compile_into(flib, &mut self.table));
+            self.sizes.push(flib.states.len());
+        }
+    }
+
+    // Recompile the flib at `index` of the compiled population after it
+    // has changed.
+    pub fn update(&mut self, index: usize, flib: &Flib) {
+        if flib.states.len() == self.sizes[index] {
+            compile_at(flib, &mut self.table, self.starts[index] as usize);
+        } else {
+            // It no longer fits in its old slot, so put it at the end.
+            self.starts[index] = compile_into(flib, &mut self.table);
+            self.sizes[index] = flib.states.len();
+        }
+    }
+
+    // Score the compiled population into `scores`, which is cleared first.
+    pub fn scores(&self, scores: &mut Vec<f32>) {
+        scores.clear();
+        let steps = self.env.num_steps() as f32;
+        for starts in self.starts.chunks(LANES) {
+            let matches = count_matches(&self.table, starts, &self.env);
+            scores.extend(matches[..starts.len()].iter().map(|&m| (m as f32) / steps));
+        }
+    }
+
+    // Score just the compiled flibs at `indexes` into `scores`, which is
+    // cleared first.
+    pub fn scores_of(&self, indexes: &[usize], scores: &mut Vec<f32>) {
+        scores.clear();
+        let steps = self.env.num_steps() as f32;
+        for chunk in indexes.chunks(LANES) {
+            let mut starts = [0; LANES];
+            for (start, &index) in starts.iter_mut().zip(chunk) {
+                *start = self.starts[index];
+            }
+            let matches = count_matches(&self.table, &starts[..chunk.len()], &self.env);
+            scores.extend(matches[..chunk.len()].iter().map(|&m| (m as f32) / steps));
+        }
+    }
+
+    // Compile and score a whole population.
+    pub fn score_population(&mut self, population: &[Flib], scores: &mut Vec<f32>) {
+        self.compile(population);
+        self.scores(scores);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::make_from_chromosome;
+    use rand::{Rng, SeedableRng, StdRng};
+
+    #[test]
+    fn test_encoded_env() {
+        let steps = |env: EncodedEnv| env.steps().collect::<Vec<_>>();
+        assert_eq!(
+            steps(EncodedEnv::new("011").unwrap()),
+            [(0, 1), (1, 1), (1, 0), (0, 1), (1, 1), (1, 0)]
+        );
+        assert_eq!(
+            steps(EncodedEnv::ahead("011", 2).unwrap())[..3],
+            [(0, 1), (1, 0), (1, 1)]
+        );
+        // Long environments span several words.
+        let long = "0111".repeat(20);
+        let encoded = EncodedEnv::new(&long).unwrap();
+        assert_eq!(encoded.inputs.len(), 3);
+        assert_eq!(encoded.steps().count(), 160);
+        assert!(encoded
+            .steps()
+            .zip(long.chars().cycle())
+            .all(|((input, _), ch)| { input == ch.to_digit(2).unwrap() as u8 }));
+        assert!(EncodedEnv::new("").is_none());
+        assert!(EncodedEnv::new("012").is_none());
+        assert!(Scorer::with_horizon("01", Horizon::Window(2)).is_none());
+    }
+
+    #[test]
+    fn test_compile_into() {
+        let flib = make_from_chromosome(String::from("0B1B1A0A"));
+        let mut table = vec![7, 7];
+        assert_eq!(compile_into(&flib, &mut table), 2);
+        // Destinations are offsets from the flib's own start.
+        assert_eq!(table[2..], [(2 << 2), (2 << 2) | 1, 1, 0]);
+    }
+
+    #[test]
+    fn test_matches_predict() {
+        let mut rng = StdRng::from_seed(&[31][..]);
+        let mut scorer_scores = vec![];
+        for _ in 0..50 {
+            let length = rng.gen_range(1, 20);
+            let environment: String = (0..length)
+                .map(|_| if rng.gen() { '1' } else { '0' })
+                .collect();
+            // Population sizes that aren't a multiple of LANES, too.
+            let size = rng.gen_range(1, 30);
+            let mut population: Vec<Flib> = (0..size)
+                .map(|_| {
+                    let mut flib = make_from_chromosome(String::new());
+                    flib.randomize(rng.gen_range(1, 10), &mut rng);
+                    flib
+                })
+                .collect();
+
+            let mut scorer = Scorer::new(&environment).unwrap();
+            scorer.score_population(&population, &mut scorer_scores);
+            let predicted: Vec<f32> = population
+                .iter_mut()
+                .map(|f| f.predict(&environment))
+                .collect();
+            assert_eq!(scorer_scores, predicted);
+            assert_eq!(scorer.score(&population[0]), predicted[0]);
+
+            let k = rng.gen_range(1, 2 * length + 2);
+            let mut scorer = Scorer::with_horizon(&environment, Horizon::Ahead(k)).unwrap();
+            scorer.score_population(&population, &mut scorer_scores);
+            let predicted: Vec<f32> = population
+                .iter_mut()
+                .map(|f| f.predict_horizon(&environment, Horizon::Ahead(k)))
+                .collect();
+            assert_eq!(scorer_scores, predicted);
+        }
+    }
+
+    #[test]
+    fn test_update() {
+        let mut population: Vec<Flib> = ["0A1A", "0B1B1A0A", "1A0A"]
+            .iter()
+            .map(|c| make_from_chromosome(c.to_string()))
+            .collect();
+        let mut scorer = Scorer::new("0110").unwrap();
+        let mut scores = vec![];
+        scorer.compile(&population);
+
+        // Same size, recompiled in place, then a different size.
+        population[0].from_chromosome(String::from("1A0A"));
+        scorer.update(0, &population[0]);
+        population[2].from_chromosome(String::from("0B1B1A0A"));
+        scorer.update(2, &population[2]);
+
+        scorer.scores(&mut scores);
+        let predicted: Vec<f32> = population.iter_mut().map(|f| f.predict("0110")).collect();
+        assert_eq!(scores, predicted);
+    }
+
+    #[test]
+    fn test_scores_of() {
+        let mut population: Vec<Flib> = ["0A1A", "0B1B1A0A", "1A0A", "0A0A"]
+            .iter()
+            .cycle()
+            .take(11)
+            .map(|c| make_from_chromosome(c.to_string()))
+            .collect();
+        let mut scorer = Scorer::new("0110").unwrap();
+        scorer.compile(&population);
+        let indexes = [10, 0, 3, 9, 1, 2, 8, 4, 5];
+        let mut scores = vec![];
+        scorer.scores_of(&indexes, &mut scores);
+        let predicted: Vec<f32> = indexes
+            .iter()
+            .map(|&i| population[i].predict("0110"))
+            .collect();
+        assert_eq!(scores, predicted);
+        scorer.scores_of(&[], &mut scores);
+        assert!(scores.is_empty());
+    }
+
+    #[test]
+    fn test_unmatchable_output() {
+        // An 'x' output never matches, exactly as in predict().
+        let mut flib = make_from_chromosome(String::from("xA1A"));
+        let mut scorer = Scorer::new("0110").unwrap();
+        assert_eq!(scorer.score(&flib), flib.predict("0110"));
+    }
+}
@@ -0,0 +1,508 @@
+// Configuration files for simulation runs.
+//
+// A config file can describe everything that simulate() needs, so that
+// experiment definitions can be checked in.  Files ending in .json are
+// parsed as JSON and anything else as TOML; for example:
+//
+//     environments = ["011001", "0011"]
+//     population = 20
+//     seed = 42
+//     horizon = "window:2"
+//     replacement = "plus:40"
+//     cache_size = 10000
+//     search = "genetic"
+//
+//     [operators]
+//     selection = "tournament:3"
+//     crossover = "uniform"
+//     mutation_rate = 0.5
+//
+//     [anneal]
+//     temperature = 0.05
+//     cooling = "exponential:0.999"
+//
+//     [stop]
+//     max_generations = 10000
+//
+//     [output]
+//     log = false
+//     stats_csv = "stats.csv"
+//     lineage_dot = "lineage.dot"
+//
+// Settings are layered: the built-in defaults, then the file, then any
+// command-line flags.  Each layer only overrides the values it actually
+// sets.  Unknown keys are an error rather than being silently ignored.
+
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::cli::SimulateArgs;
+use crate::local_search::{Annealing, Cooling};
+use crate::{Crossover, Horizon, Operators, Replacement, Search, Selection, SimParams, MAX_STATES};
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    // Environments to predict; each gets a run of its own.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub environments: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub population: Option<usize>,
+    // Defaults to half the environment's length, plus one.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub states: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<u64>,
+    // Which future symbols outputs are judged against; ahead:1 by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub horizon: Option<Horizon>,
+    // steady-state (the default), generational, comma:LAMBDA or
+    // plus:LAMBDA.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub replacement: Option<Replacement>,
+    // Number of scores the fitness cache holds; 0 turns it off.  Only
+    // window horizons have a cache, so setting it for others is an error.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_size: Option<usize>,
+    // genetic (the default), hill-climb or anneal.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub search: Option<Search>,
+    #[serde(default)]
+    pub operators: OperatorsConfig,
+    #[serde(default)]
+    pub anneal: AnnealConfig,
+    #[serde(default)]
+    pub stop: StopConfig,
+    #[serde(default)]
+    pub output: OutputConfig,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OperatorsConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selection: Option<Selection>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub crossover: Option<Crossover>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mutation_rate: Option<f32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnealConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f32>,
+    // exponential:RATE, linear or logarithmic.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cooling: Option<Cooling>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct StopConfig {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_score: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_generations: Option<usize>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct OutputConfig {
+    // Print the population after every generation; on by default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stats_csv: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result_json: Option<PathBuf>,
+    // Compare each run's best flib with this many random flibs.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub significance: Option<usize>,
+    // Write the ancestry of each run's best flib as DOT or JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage_dot: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lineage_json: Option<PathBuf>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Config, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("can't read config file {}: {}", path.display(), e))?;
+        let parsed = if path.extension() == Some(OsStr::new("json")) {
+            serde_json::from_str(&text).map_err(|e| e.to_string())
+        } else {
+            toml::from_str(&text).map_err(|e| e.to_string())
+        };
+        parsed.map_err(|e| format!("invalid config file {}: {}", path.display(), e))
+    }
+
+    // The settings given as command-line flags, as a layer of their own.
+    pub fn from_args(args: &SimulateArgs) -> Config {
+        Config {
+            environments: args.environments.clone(),
+            population: args.population,
+            states: args.states,
+            seed: args.seed,
+            horizon: args.horizon,
+            replacement: args.replacement,
+            cache_size: if args.no_cache {
+                Some(0)
+            } else {
+                args.cache_size
+            },
+            search: args.search,
+            anneal: AnnealConfig {
+                temperature: args.temperature,
+                cooling: args.cooling,
+            },
+            operators: OperatorsConfig {
+                selection: args.selection,
+                crossover: args.crossover,
+                mutation_rate: args.mutation_rate,
+            },
+            stop: StopConfig {
+                target_score: args.target_score,
+                max_generations: args.max_generations,
+            },
+            output: OutputConfig {
+                log: if args.quiet { Some(false) } else { None },
+                stats_csv: args.stats_csv.clone(),
+                result_json: args.result_json.clone(),
+                significance: args.significance,
+                lineage_dot: args.lineage_dot.clone(),
+                lineage_json: args.lineage_json.clone(),
+            },
+        }
+    }
+
+    // Load the --config file, if there is one, and lay the other flags
+    // over it.
+    pub fn from_file_and_args(args: &SimulateArgs) -> Result<Config, String> {
+        let base = match &args.config {
+            Some(path) => Config::load(path)?,
+            None => Config::default(),
+        };
+        let config = base.merge(Config::from_args(args));
+        config.validate()?;
+        Ok(config)
+    }
+
+    // Lay `overrides` over this config.  Any value set in `overrides` wins.
+    pub fn merge(self, overrides: Config) -> Config {
+        Config {
+            environments: overrides.environments.or(self.environments),
+            population: overrides.population.or(self.population),
+            states: overrides.states.or(self.states),
+            seed: overrides.seed.or(self.seed),
+            horizon: overrides.horizon.or(self.horizon),
+            replacement: overrides.replacement.or(self.replacement),
+            cache_size: overrides.cache_size.or(self.cache_size),
+            search: overrides.search.or(self.search),
+            anneal: AnnealConfig {
+                temperature: overrides.anneal.temperature.or(self.anneal.temperature),
+                cooling: overrides.anneal.cooling.or(self.anneal.cooling),
+            },
+            operators: OperatorsConfig {
+                selection: overrides.operators.selection.or(self.operators.selection),
+                crossover: overrides.operators.crossover.or(self.operators.crossover),
+                mutation_rate: overrides
+                    .operators
+                    .mutation_rate
+                    .or(self.operators.mutation_rate),
+            },
+            stop: StopConfig {
+                target_score: overrides.stop.target_score.or(self.stop.target_score),
+                max_generations: overrides.stop.max_generations.or(self.stop.max_generations),
+            },
+            output: OutputConfig {
+                log: overrides.output.log.or(self.output.log),
+                stats_csv: overrides.output.stats_csv.or(self.output.stats_csv),
+                result_json: overrides.output.result_json.or(self.output.result_json),
+                significance: overrides.output.significance.or(self.output.significance),
+                lineage_dot: overrides.output.lineage_dot.or(self.output.lineage_dot),
+                lineage_json: overrides.output.lineage_json.or(self.output.lineage_json),
+            },
+        }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        for env in self.environments.iter().flatten() {
+            if env.is_empty() || env.chars().any(|ch| ch != '0' && ch != '1') {
+                return Err(format!(
+                    "environment '{}' must be a non-empty string of 0s and 1s",
+                    env
+                ));
+            }
+        }
+        if matches!(&self.environments, Some(envs) if envs.is_empty()) {
+            return Err(String::from("environments must not be empty"));
+        }
+        if self.population == Some(0) {
+            return Err(String::from("population must be at least 1"));
+        }
+        if self.states == Some(0) {
+            return Err(String::from("states must be at least 1"));
+        }
+        if let Some(states) = self.states.filter(|&states| states > MAX_STATES) {
+            return Err(format!(
+                "states must be at most {}, not {}",
+                MAX_STATES, states
+            ));
+        }
+        if self.states.is_none() {
+            // The default of half the environment's length, plus one, can
+            // be too many for a long environment.
+            for env in self.environments.iter().flatten() {
+                if env.len() / 2 + 1 > MAX_STATES {
+                    return Err(format!(
+                        "environment '{}' would get {} states by default; set states \
+                         to at most {}",
+                        env,
+                        env.len() / 2 + 1,
+                        MAX_STATES
+                    ));
+                }
+            }
+        }
+        if let Some(rate) = self.operators.mutation_rate {
+            if !(0.0..=1.0).contains(&rate) {
+                return Err(format!("mutation_rate {} is outside 0.0-1.0", rate));
+            }
+        }
+        if let Some(Replacement::Comma(lambda)) = self.replacement {
+            let population = self
+                .population
+                .unwrap_or(SimParams::default().population_size);
+            if lambda < population {
+                return Err(format!(
+                    "comma:{} needs at least as many offspring as the population of {}",
+                    lambda, population
+                ));
+            }
+        }
+        if let Some(temperature) = self.anneal.temperature {
+            if !(temperature > 0.0 && temperature.is_finite()) {
+                return Err(format!(
+                    "temperature {} must be a positive number",
+                    temperature
+                ));
+            }
+        }
+        if self.anneal.cooling == Some(Cooling::Linear) && self.stop.max_generations.is_none() {
+            return Err(String::from("linear cooling needs max_generations"));
+        }
+        if let (Some(_), Horizon::Ahead(k)) = (self.cache_size, self.horizon.unwrap_or_default()) {
+            return Err(format!(
+                "the fitness cache is only for window horizons; ahead:{} rescores \
+                 just the flibs that change, so drop cache_size and --no-cache",
+                k
+            ));
+        }
+        if self.output.significance == Some(0) {
+            return Err(String::from("significance must be at least 1 sample"));
+        }
+        if let Some(target) = self.stop.target_score {
+            if !(target > 0.0 && target <= 1.0) {
+                return Err(format!("target_score {} is outside 0.0-1.0", target));
+            }
+        }
+        Ok(())
+    }
+
+    // One set of simulation parameters per environment, with defaults
+    // filled in.
+    pub fn params(&self) -> Vec<SimParams> {
+        let defaults = SimParams::default();
+        let environments = self
+            .environments
+            .clone()
+            .unwrap_or_else(|| vec![defaults.environment.clone()]);
+        environments
+            .into_iter()
+            .map(|environment| SimParams {
+                num_states: self.states.unwrap_or(environment.len() / 2 + 1),
+                environment,
+                population_size: self.population.unwrap_or(defaults.population_size),
+                operators: Operators {
+                    selection: self
+                        .operators
+                        .selection
+                        .unwrap_or(defaults.operators.selection),
+                    crossover: self
+                        .operators
+                        .crossover
+                        .unwrap_or(defaults.operators.crossover),
+                    mutation_rate: self
+                        .operators
+                        .mutation_rate
+                        .unwrap_or(defaults.operators.mutation_rate),
+                },
+                replacement: self.replacement.unwrap_or(defaults.replacement),
+                search: self.search.unwrap_or(defaults.search),
+                annealing: Annealing {
+                    temperature: self
+                        .anneal
+                        .temperature
+                        .unwrap_or(defaults.annealing.temperature),
+                    cooling: self.anneal.cooling.unwrap_or(defaults.annealing.cooling),
+                },
+                horizon: self.horizon.unwrap_or(defaults.horizon),
+                target_score: self.stop.target_score.unwrap_or(defaults.target_score),
+                max_generations: self.stop.max_generations.or(defaults.max_generations),
+                cache_size: self.cache_size.unwrap_or(defaults.cache_size),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXAMPLE: &str = r#"
+        environments = ["0011"]
+        population = 20
+        seed = 42
+
+        [operators]
+        selection = "tournament:3"
+        mutation_rate = 0.5
+
+        [stop]
+        max_generations = 100
+    "#;
+
+    #[test]
+    fn test_parse_toml() {
+        let config: Config = toml::from_str(EXAMPLE).unwrap();
+        let params = config.params();
+        assert_eq!(params.len(), 1);
+        assert_eq!(params[0].environment, "0011");
+        assert_eq!(params[0].num_states, 3);
+        assert_eq!(params[0].population_size, 20);
+        assert_eq!(params[0].operators.selection, Selection::Tournament(3));
+        assert_eq!(params[0].operators.crossover, Crossover::OnePoint);
+        assert_eq!(params[0].max_generations, Some(100));
+        assert_eq!(config.seed, Some(42));
+    }
+
+    #[test]
+    fn test_json_matches_toml() {
+        let json = r#"{
+            "environments": ["0011"], "population": 20, "seed": 42,
+            "operators": {"selection": "tournament:3", "mutation_rate": 0.5},
+            "stop": {"max_generations": 100}
+        }"#;
+        let from_json: Config = serde_json::from_str(json).unwrap();
+        let from_toml: Config = toml::from_str(EXAMPLE).unwrap();
+        assert_eq!(from_json, from_toml);
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        let err = toml::from_str::<Config>("populaton = 20").unwrap_err();
+        assert!(err.to_string().contains("unknown field `populaton`"));
+        let err = toml::from_str::<Config>("[operators]\nmutation = 0.1").unwrap_err();
+        assert!(err.to_string().contains("unknown field `mutation`"));
+        let err = toml::from_str::<Config>("[operators]\ncrossover = \"two-point\"").unwrap_err();
+        assert!(err.to_string().contains("unknown crossover"));
+    }
+
+    #[test]
+    fn test_merge_overrides() {
+        let file: Config = toml::from_str(EXAMPLE).unwrap();
+        let flags = Config {
+            population: Some(5),
+            operators: OperatorsConfig {
+                crossover: Some(Crossover::Uniform),
+                ..OperatorsConfig::default()
+            },
+            ..Config::default()
+        };
+        let merged = file.merge(flags);
+        assert_eq!(merged.population, Some(5));
+        assert_eq!(merged.seed, Some(42));
+        assert_eq!(merged.operators.crossover, Some(Crossover::Uniform));
+        assert_eq!(merged.operators.selection, Some(Selection::Tournament(3)));
+    }
+
+    #[test]
+    fn test_no_cache_flag() {
+        let file: Config = toml::from_str("cache_size = 100").unwrap();
+        assert_eq!(file.params()[0].cache_size, 100);
+        let args = SimulateArgs {
+            no_cache: true,
+            ..SimulateArgs::default()
+        };
+        let merged = file.merge(Config::from_args(&args));
+        assert_eq!(merged.params()[0].cache_size, 0);
+        // Only window horizons have a cache to size.
+        assert!(merged.validate().is_err());
+        let window: Config = toml::from_str("cache_size = 100\nhorizon = \"window:2\"").unwrap();
+        assert!(window.merge(Config::from_args(&args)).validate().is_ok());
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut config = Config::default();
+        assert!(config.validate().is_ok());
+        config.environments = Some(vec![String::from("0120")]);
+        assert!(config.validate().is_err());
+        config.environments = None;
+        config.operators.mutation_rate = Some(1.5);
+        assert!(config.validate().is_err());
+        config.operators.mutation_rate = None;
+        config.replacement = Some(Replacement::Comma(5));
+        assert!(config.validate().unwrap_err().contains("population of 10"));
+        config.population = Some(5);
+        assert!(config.validate().is_ok());
+        config.states = Some(27);
+        assert!(config.validate().unwrap_err().contains("at most 26"));
+        config.states = None;
+        config.environments = Some(vec!["01".repeat(26)]);
+        assert!(config
+            .validate()
+            .unwrap_err()
+            .contains("27 states by default"));
+        config.states = Some(26);
+        assert!(config.validate().is_ok());
+        config.environments = None;
+        config.states = None;
+        config.anneal.cooling = Some(Cooling::Linear);
+        assert!(config.validate().unwrap_err().contains("max_generations"));
+        config.stop.max_generations = Some(100);
+        assert!(config.validate().is_ok());
+        config.anneal.temperature = Some(0.0);
+        assert!(config.validate().is_err());
+    }
+
+    #[test]
+    fn test_search_settings() {
+        let config: Config =
+            toml::from_str("search = \"anneal\"\n[anneal]\ncooling = \"exponential:0.99\"\n")
+                .unwrap();
+        let params = &config.params()[0];
+        assert_eq!(params.search, Search::Anneal);
+        assert_eq!(params.annealing.cooling, Cooling::Exponential(0.99));
+        assert_eq!(
+            params.annealing.temperature,
+            Annealing::default().temperature
+        );
+        let text = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+        assert!(toml::from_str::<Config>("search = \"tabu\"").is_err());
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let config: Config = toml::from_str(EXAMPLE).unwrap();
+        let text = toml::to_string(&config).unwrap();
+        assert_eq!(toml::from_str::<Config>(&text).unwrap(), config);
+    }
+}
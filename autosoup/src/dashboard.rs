@@ -0,0 +1,358 @@
+// Live terminal dashboard for a running simulation.
+//
+// The dashboard is an Observer, so simulate() drives it: after each
+// generation it records the scores, redraws the screen if enough time has
+// passed, and handles any keypresses.  While paused it blocks inside
+// generation() until the user resumes, steps, or quits.
+
+use std::io::{self, Stdout};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use crossterm::event::{self, Event, KeyCode};
+use crossterm::execute;
+use crossterm::terminal::{
+    disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen,
+};
+use serde::Serialize;
+use tui::backend::CrosstermBackend;
+use tui::layout::{Constraint, Direction, Layout};
+use tui::text::{Span, Spans};
+use tui::widgets::{Block, Borders, Gauge, Paragraph, Sparkline};
+use tui::Terminal;
+
+use crate::{
+    diversity, find_minmax, make_from_chromosome, state_to_char, Control, Horizon, Observer, Report,
+};
+
+// Redrawing after every generation would slow the run to a crawl.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(50);
+const RATE_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Serialize)]
+struct Checkpoint<'a> {
+    generation: usize,
+    environment: &'a str,
+    population: &'a [String],
+    scores: &'a [f32],
+}
+
+pub struct Dashboard {
+    terminal: Terminal<CrosstermBackend<Stdout>>,
+    checkpoint_path: PathBuf,
+    horizon: Horizon,
+    paused: bool,
+    status: String,
+
+    // Best and mean score of each generation so far, as percentages.
+    best_history: Vec<u64>,
+    mean_history: Vec<u64>,
+
+    // The most recent generation.
+    generation: usize,
+    environment: String,
+    chromosomes: Vec<String>,
+    scores: Vec<f32>,
+
+    // Generations per second, measured over the last RATE_INTERVAL.
+    rate: f64,
+    rate_sample: (Instant, usize),
+    last_draw: Instant,
+}
+
+// Lines describing how `chromosome` runs over the doubled environment:
+// the state it was in, the input it saw, what it output, and what it
+// should have output under `horizon`.  A window's forecast starts with the
+// next symbol, so that's the one shown for it.
+fn trace(chromosome: &str, environment: &str, horizon: Horizon) -> Vec<String> {
+    let mut flib = make_from_chromosome(chromosome.to_string());
+    let inputs = environment.to_owned() + environment;
+    let ahead = match horizon {
+        Horizon::Ahead(k) => k,
+        Horizon::Window(_) => 1,
+    };
+    let env: Vec<char> = environment.chars().collect();
+    let expected: String = (0..inputs.len())
+        .map(|i| env[(i + ahead) % env.len()])
+        .collect();
+
+    let mut states = String::new();
+    let mut outputs = String::new();
+    let mut matches = String::new();
+    flib.current_state = 0;
+    for (input, wanted) in inputs.chars().zip(expected.chars()) {
+        states.push(state_to_char(flib.current_state));
+        let output = flib.transition(input);
+        outputs.push(output);
+        matches.push(if output == wanted { '^' } else { ' ' });
+    }
+
+    vec![
+        format!("input:    {}", inputs),
+        format!("state:    {}", states),
+        format!("output:   {}", outputs),
+        format!("expected: {}", expected),
+        format!("          {}", matches),
+    ]
+}
+
+impl Dashboard {
+    pub fn new(checkpoint_path: PathBuf, horizon: Horizon) -> crossterm::Result<Dashboard> {
+        enable_raw_mode()?;
+        let terminal = Dashboard::setup_terminal().inspect_err(|_| {
+            // Don't leave the terminal half set up when we can't draw on it.
+            let _ = disable_raw_mode();
+            let _ = execute!(io::stdout(), LeaveAlternateScreen);
+        })?;
+
+        let now = Instant::now();
+        Ok(Dashboard {
+            terminal,
+            checkpoint_path,
+            horizon,
+            paused: false,
+            status: String::from("Running"),
+            best_history: vec![],
+            mean_history: vec![],
+            generation: 0,
+            environment: String::new(),
+            chromosomes: vec![],
+            scores: vec![],
+            rate: 0.0,
+            rate_sample: (now, 0),
+            last_draw: now,
+        })
+    }
+
+    fn setup_terminal() -> crossterm::Result<Terminal<CrosstermBackend<Stdout>>> {
+        let mut stdout = io::stdout();
+        execute!(stdout, EnterAlternateScreen)?;
+        let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+        terminal.hide_cursor()?;
+        terminal.clear()?;
+        Ok(terminal)
+    }
+
+    fn record(&mut self, report: &Report) {
+        let (_, max_index) = find_minmax(report.scores);
+        let mean = report.scores.iter().sum::<f32>() / (report.scores.len() as f32);
+        self.best_history
+            .push((report.scores[max_index] * 100.0).round() as u64);
+        self.mean_history.push((mean * 100.0).round() as u64);
+
+        self.generation = report.generation;
+        self.environment = report.environment.to_string();
+        self.chromosomes = report
+            .population
+            .iter()
+            .map(|f| f.as_chromosome())
+            .collect();
+        self.scores = report.scores.to_vec();
+
+        let (since, start_generation) = self.rate_sample;
+        if !self.paused && since.elapsed() >= RATE_INTERVAL {
+            self.rate =
+                ((self.generation - start_generation) as f64) / since.elapsed().as_secs_f64();
+            self.rate_sample = (Instant::now(), self.generation);
+        }
+    }
+
+    fn save_checkpoint(&mut self) {
+        let checkpoint = Checkpoint {
+            generation: self.generation,
+            environment: &self.environment,
+            population: &self.chromosomes,
+            scores: &self.scores,
+        };
+        let json =
+            serde_json::to_string_pretty(&checkpoint).expect("Failed to serialize checkpoint");
+        self.status = match std::fs::write(&self.checkpoint_path, json) {
+            Ok(()) => format!(
+                "Saved generation {} to {}",
+                self.generation,
+                self.checkpoint_path.display()
+            ),
+            Err(e) => format!("Failed to save checkpoint: {}", e),
+        };
+    }
+
+    fn draw(&mut self) {
+        self.last_draw = Instant::now();
+
+        let (_, best) = find_minmax(&self.scores);
+        let state = if self.paused { "PAUSED" } else { "running" };
+        let header = vec![
+            Spans::from(format!(
+                "Generation {}   {:.0} gen/s   {}",
+                self.generation, self.rate, state
+            )),
+            Spans::from("p: pause/resume   s: step   c: save checkpoint   q: quit"),
+        ];
+        let mut best_lines = vec![Spans::from(format!(
+            "{}  score {:.3}",
+            self.chromosomes[best], self.scores[best]
+        ))];
+        best_lines.extend(
+            trace(&self.chromosomes[best], &self.environment, self.horizon)
+                .into_iter()
+                .map(|line| Spans::from(Span::raw(line))),
+        );
+        let diversity = diversity(&self.chromosomes);
+
+        let best_history = &self.best_history;
+        let mean_history = &self.mean_history;
+        let status = &self.status;
+        self.terminal
+            .draw(|f| {
+                let chunks = Layout::default()
+                    .direction(Direction::Vertical)
+                    .constraints(
+                        [
+                            Constraint::Length(4),
+                            Constraint::Length(6),
+                            Constraint::Length(6),
+                            Constraint::Length(3),
+                            Constraint::Length(8),
+                            Constraint::Min(3),
+                        ]
+                        .as_ref(),
+                    )
+                    .split(f.size());
+
+                // Only show as much history as fits in the sparkline.
+                let width = chunks[1].width.saturating_sub(2) as usize;
+                let recent = |history: &'_ [u64]| -> Vec<u64> {
+                    history[history.len().saturating_sub(width)..].to_vec()
+                };
+                let best_data = recent(best_history);
+                let mean_data = recent(mean_history);
+
+                f.render_widget(
+                    Paragraph::new(header)
+                        .block(Block::default().title("autosoup").borders(Borders::ALL)),
+                    chunks[0],
+                );
+                f.render_widget(
+                    Sparkline::default()
+                        .block(Block::default().title("Best score").borders(Borders::ALL))
+                        .data(&best_data)
+                        .max(100),
+                    chunks[1],
+                );
+                f.render_widget(
+                    Sparkline::default()
+                        .block(Block::default().title("Mean score").borders(Borders::ALL))
+                        .data(&mean_data)
+                        .max(100),
+                    chunks[2],
+                );
+                f.render_widget(
+                    Gauge::default()
+                        .block(Block::default().title("Diversity").borders(Borders::ALL))
+                        .ratio(f64::from(diversity).clamp(0.0, 1.0))
+                        .label(format!("{:.3}", diversity)),
+                    chunks[3],
+                );
+                f.render_widget(
+                    Paragraph::new(best_lines)
+                        .block(Block::default().title("Best flib").borders(Borders::ALL)),
+                    chunks[4],
+                );
+                f.render_widget(
+                    Paragraph::new(status.as_str())
+                        .block(Block::default().title("Status").borders(Borders::ALL)),
+                    chunks[5],
+                );
+            })
+            .expect("Failed to draw dashboard");
+    }
+
+    // Show the outcome of the run and wait for the user to quit.
+    pub fn finish(&mut self, perfect: &Option<String>) {
+        self.paused = true;
+        self.status = match perfect {
+            Some(chromosome) => format!("Perfect predictor: {}   (q to quit)", chromosome),
+            None => String::from("Stopped without a perfect predictor   (q to quit)"),
+        };
+        loop {
+            self.draw();
+            if let Ok(Event::Key(key)) = event::read() {
+                match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return,
+                    KeyCode::Char('c') => self.save_checkpoint(),
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+impl Observer for Dashboard {
+    fn generation(&mut self, report: &Report) -> Control {
+        self.record(report);
+        if !self.paused && self.last_draw.elapsed() < REDRAW_INTERVAL {
+            return Control::Continue;
+        }
+
+        loop {
+            self.draw();
+            let wait = if self.paused {
+                Duration::from_millis(250)
+            } else {
+                Duration::from_millis(0)
+            };
+            if event::poll(wait).unwrap_or(false) {
+                if let Ok(Event::Key(key)) = event::read() {
+                    match key.code {
+                        KeyCode::Char('q') | KeyCode::Esc => return Control::Stop,
+                        KeyCode::Char('p') | KeyCode::Char(' ') => {
+                            self.paused = !self.paused;
+                            self.status =
+                                String::from(if self.paused { "Paused" } else { "Running" });
+                            self.rate_sample = (Instant::now(), self.generation);
+                        }
+                        // Advance by exactly one generation, staying paused.
+                        KeyCode::Char('s') if self.paused => return Control::Continue,
+                        KeyCode::Char('c') => self.save_checkpoint(),
+                        _ => {}
+                    }
+                    continue;
+                }
+            }
+            if !self.paused {
+                return Control::Continue;
+            }
+        }
+    }
+}
+
+impl Drop for Dashboard {
+    fn drop(&mut self) {
+        // Put the terminal back the way we found it.
+        let _ = disable_raw_mode();
+        let _ = execute!(self.terminal.backend_mut(), LeaveAlternateScreen);
+        let _ = self.terminal.show_cursor();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_trace() {
+        // The echo flib outputs each input, so it's right whenever the next
+        // symbol repeats the current one.
+        let lines = trace("0A1A", "0011", Horizon::Ahead(1));
+        assert_eq!(lines[0], "input:    00110011");
+        assert_eq!(lines[1], "state:    AAAAAAAA");
+        assert_eq!(lines[2], "output:   00110011");
+        assert_eq!(lines[3], "expected: 01100110");
+        assert_eq!(lines[4], "          ^ ^ ^ ^ ");
+
+        // Two steps ahead, the echo flib is always wrong on this one.
+        let lines = trace("0A1A", "0011", Horizon::Ahead(2));
+        assert_eq!(lines[3], "expected: 11001100");
+        assert_eq!(lines[4], "                  ");
+    }
+}
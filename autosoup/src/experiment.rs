@@ -0,0 +1,375 @@
+// Batch experiments: run simulate() for every combination in a grid of
+// settings, with many seeds each, and summarize how reliably and how
+// quickly each setting finds a perfect predictor.
+
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+
+use rayon::prelude::*;
+
+use crate::output::file_error;
+use crate::{seeded_rng, simulate, Crossover, Operators, Selection, SilentObserver, SimParams};
+
+pub struct Grid {
+    pub environments: Vec<String>,
+    pub population_sizes: Vec<usize>,
+    pub selections: Vec<Selection>,
+    pub crossovers: Vec<Crossover>,
+    pub mutation_rates: Vec<f32>,
+    pub seeds: Vec<u64>,
+    pub max_generations: usize,
+}
+
+impl Grid {
+    // Every combination of settings in the grid, not counting seeds.
+    fn settings(&self) -> Vec<SimParams> {
+        let mut settings = vec![];
+        for environment in &self.environments {
+            for &population_size in &self.population_sizes {
+                for &selection in &self.selections {
+                    for &crossover in &self.crossovers {
+                        for &mutation_rate in &self.mutation_rates {
+                            settings.push(SimParams {
+                                environment: environment.clone(),
+                                population_size,
+                                num_states: environment.len() / 2 + 1,
+                                operators: Operators {
+                                    selection,
+                                    crossover,
+                                    mutation_rate,
+                                },
+                                max_generations: Some(self.max_generations),
+                                ..SimParams::default()
+                            });
+                        }
+                    }
+                }
+            }
+        }
+        settings
+    }
+}
+
+// Result of a single seeded run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RunResult {
+    // Index into the grid's settings.
+    pub setting: usize,
+    pub seed: u64,
+    pub solved: bool,
+    pub generations: usize,
+}
+
+pub fn run_once(params: &SimParams, seed: u64) -> (bool, usize) {
+    let mut rng = seeded_rng(seed);
+    let outcome = simulate(params, &mut rng, &mut SilentObserver);
+    (outcome.solution.is_some(), outcome.generation)
+}
+
+// Run every setting with every seed, in parallel.
+pub fn run_grid(settings: &[SimParams], seeds: &[u64]) -> Vec<RunResult> {
+    let jobs: Vec<(usize, u64)> = (0..settings.len())
+        .flat_map(|setting| seeds.iter().map(move |&seed| (setting, seed)))
+        .collect();
+    jobs.par_iter()
+        .map(|&(setting, seed)| {
+            let (solved, generations) = run_once(&settings[setting], seed);
+            RunResult {
+                setting,
+                seed,
+                solved,
+                generations,
+            }
+        })
+        .collect()
+}
+
+// Two-sided 95% critical value of Student's t distribution with `df`
+// degrees of freedom.  Past 30 the normal approximation is close enough.
+fn t_critical(df: usize) -> f64 {
+    const TABLE: [f64; 30] = [
+        12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+        2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+        2.052, 2.048, 2.045, 2.042,
+    ];
+    match df {
+        0 => f64::NAN,
+        1..=30 => TABLE[df - 1],
+        _ => 1.960,
+    }
+}
+
+// 95% Wilson score interval for a success rate of `successes` out of `n`.
+fn wilson_interval(successes: usize, n: usize) -> (f64, f64) {
+    if n == 0 {
+        return (f64::NAN, f64::NAN);
+    }
+    let z = 1.960;
+    let n = n as f64;
+    let p = (successes as f64) / n;
+    let denominator = 1.0 + z * z / n;
+    let center = (p + z * z / (2.0 * n)) / denominator;
+    let half_width = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / denominator;
+    (center - half_width, center + half_width)
+}
+
+fn median(values: &[usize]) -> f64 {
+    if values.is_empty() {
+        return f64::NAN;
+    }
+    let mut sorted = values.to_vec();
+    sorted.sort_unstable();
+    let mid = sorted.len() / 2;
+    if sorted.len().is_multiple_of(2) {
+        (sorted[mid - 1] + sorted[mid]) as f64 / 2.0
+    } else {
+        sorted[mid] as f64
+    }
+}
+
+// Statistics for one setting over all of its seeds.  The generation
+// statistics only count runs that found a perfect predictor.
+#[derive(Debug)]
+pub struct Summary {
+    pub runs: usize,
+    pub successes: usize,
+    pub success_rate: f64,
+    pub success_interval: (f64, f64),
+    pub mean_generations: f64,
+    pub mean_interval: (f64, f64),
+    pub median_generations: f64,
+}
+
+pub fn summarize(results: &[RunResult]) -> Summary {
+    let solved: Vec<usize> = results
+        .iter()
+        .filter(|r| r.solved)
+        .map(|r| r.generations)
+        .collect();
+    let k = solved.len();
+    let mean = solved.iter().sum::<usize>() as f64 / (k as f64);
+    let mean_interval = if k >= 2 {
+        let variance = solved
+            .iter()
+            .map(|&g| (g as f64 - mean).powi(2))
+            .sum::<f64>()
+            / ((k - 1) as f64);
+        let half_width = t_critical(k - 1) * (variance / (k as f64)).sqrt();
+        (mean - half_width, mean + half_width)
+    } else {
+        (f64::NAN, f64::NAN)
+    };
+
+    Summary {
+        runs: results.len(),
+        successes: k,
+        success_rate: (k as f64) / (results.len() as f64),
+        success_interval: wilson_interval(k, results.len()),
+        mean_generations: mean,
+        mean_interval,
+        median_generations: median(&solved),
+    }
+}
+
+fn setting_columns(params: &SimParams) -> [String; 5] {
+    [
+        params.environment.clone(),
+        params.population_size.to_string(),
+        params.operators.selection.to_string(),
+        params.operators.crossover.to_string(),
+        params.operators.mutation_rate.to_string(),
+    ]
+}
+
+pub fn write_runs_csv(
+    path: &Path,
+    settings: &[SimParams],
+    results: &[RunResult],
+) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "environment,population,selection,crossover,mutation_rate,seed,solved,generations"
+    )?;
+    for r in results {
+        writeln!(
+            file,
+            "{},{},{},{}",
+            setting_columns(&settings[r.setting]).join(","),
+            r.seed,
+            r.solved,
+            r.generations
+        )?;
+    }
+    Ok(())
+}
+
+pub fn write_summary_csv(
+    path: &Path,
+    settings: &[SimParams],
+    summaries: &[Summary],
+) -> io::Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(
+        file,
+        "environment,population,selection,crossover,mutation_rate,runs,successes,\
+         success_rate,success_low,success_high,mean_generations,mean_low,mean_high,\
+         median_generations"
+    )?;
+    for (params, s) in settings.iter().zip(summaries) {
+        writeln!(
+            file,
+            "{},{},{},{},{},{},{},{},{},{}",
+            setting_columns(params).join(","),
+            s.runs,
+            s.successes,
+            s.success_rate,
+            s.success_interval.0,
+            s.success_interval.1,
+            s.mean_generations,
+            s.mean_interval.0,
+            s.mean_interval.1,
+            s.median_generations
+        )?;
+    }
+    Ok(())
+}
+
+pub fn summary_table(settings: &[SimParams], summaries: &[Summary]) -> String {
+    let env_width = settings
+        .iter()
+        .map(|p| p.environment.len())
+        .max()
+        .unwrap_or(0)
+        .max("environment".len());
+    let mut table = format!(
+        "{:<ew$}  {:>4}  {:<14}  {:<9}  {:>5}  {:>11}  {:>13}  {:>8}  {:>17}  {:>8}\n",
+        "environment",
+        "pop",
+        "selection",
+        "crossover",
+        "mut",
+        "success",
+        "95% CI",
+        "mean gen",
+        "95% CI",
+        "median",
+        ew = env_width
+    );
+    for (params, s) in settings.iter().zip(summaries) {
+        table.push_str(&format!(
+            "{:<ew$}  {:>4}  {:<14}  {:<9}  {:>5}  {:>5}/{:<5}  {:>5.2}-{:<5.2}  {:>8.1}  {:>8.1}-{:<8.1}  {:>8.1}\n",
+            params.environment,
+            params.population_size,
+            params.operators.selection.to_string(),
+            params.operators.crossover.to_string(),
+            params.operators.mutation_rate,
+            s.successes,
+            s.runs,
+            s.success_interval.0,
+            s.success_interval.1,
+            s.mean_generations,
+            s.mean_interval.0,
+            s.mean_interval.1,
+            s.median_generations,
+            ew = env_width
+        ));
+    }
+    table
+}
+
+// Run the whole grid, print a summary table, and write any CSV files.
+pub fn run(grid: &Grid, runs_csv: Option<&Path>, summary_csv: Option<&Path>) -> Result<(), String> {
+    let settings = grid.settings();
+    let results = run_grid(&settings, &grid.seeds);
+    let summaries: Vec<Summary> = (0..settings.len())
+        .map(|i| {
+            let runs: Vec<RunResult> = results.iter().filter(|r| r.setting == i).cloned().collect();
+            summarize(&runs)
+        })
+        .collect();
+
+    print!("{}", summary_table(&settings, &summaries));
+    if let Some(path) = runs_csv {
+        write_runs_csv(path, &settings, &results).map_err(|e| file_error(path, e))?;
+    }
+    if let Some(path) = summary_csv {
+        write_summary_csv(path, &settings, &summaries).map_err(|e| file_error(path, e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(solved: bool, generations: usize) -> RunResult {
+        RunResult {
+            setting: 0,
+            seed: 0,
+            solved,
+            generations,
+        }
+    }
+
+    #[test]
+    fn test_wilson_interval() {
+        let (low, high) = wilson_interval(8, 10);
+        assert!((low - 0.4902).abs() < 1e-3);
+        assert!((high - 0.9433).abs() < 1e-3);
+        let (low, high) = wilson_interval(0, 10);
+        assert!(low.abs() < 1e-9);
+        assert!(high > 0.0 && high < 0.5);
+    }
+
+    #[test]
+    fn test_summarize() {
+        let results = vec![
+            result(true, 10),
+            result(true, 20),
+            result(true, 60),
+            result(false, 1000),
+        ];
+        let summary = summarize(&results);
+        assert_eq!(summary.runs, 4);
+        assert_eq!(summary.successes, 3);
+        assert_eq!(summary.success_rate, 0.75);
+        assert_eq!(summary.mean_generations, 30.0);
+        assert_eq!(summary.median_generations, 20.0);
+        // sd = 26.458, so the half-width is 4.303 * 26.458 / sqrt(3).
+        assert!((summary.mean_interval.1 - 95.73).abs() < 0.01);
+    }
+
+    #[test]
+    fn test_grid_settings() {
+        let grid = Grid {
+            environments: vec!["011001".to_string(), "0011".to_string()],
+            population_sizes: vec![10, 20],
+            selections: vec![Selection::WorstBest, Selection::Tournament(3)],
+            crossovers: vec![Crossover::OnePoint],
+            mutation_rates: vec![0.5, 1.0],
+            seeds: vec![1, 2, 3],
+            max_generations: 100,
+        };
+        let settings = grid.settings();
+        assert_eq!(settings.len(), 16);
+        assert_eq!(settings[8].environment, "0011");
+        assert_eq!(settings[8].num_states, 3);
+    }
+
+    #[test]
+    fn test_seeded_runs_are_reproducible() {
+        let params = SimParams {
+            max_generations: Some(500),
+            ..SimParams::default()
+        };
+        let results = run_grid(std::slice::from_ref(&params), &[7, 7, 8]);
+        assert_eq!(results[0].generations, results[1].generations);
+        assert_eq!(
+            run_once(&params, 7),
+            (results[0].solved, results[0].generations)
+        );
+        assert!(results.iter().all(|r| r.generations <= 500));
+    }
+}
@@ -0,0 +1,487 @@
+// Import and export of flibs in formats that other automata tools read.
+//
+// * JSON: an object listing the number of states, the initial state, and
+//   every transition as {"from", "input", "output", "to"}.
+//
+// * HOA, the Hanoi Omega-Automata format, version 1.  The machine is a
+//   Mealy machine over two atomic propositions, "i" (the input) and "o"
+//   (the controllable output), which the importer finds by name in the AP
+//   header.  Each edge's label fixes both, and every run is accepting.
+//
+// * KISS2, the Mealy table format used by logic synthesis tools, with one
+//   input bit and one output bit.  Each line is input, current state, next
+//   state, output.
+//
+// Imported machines may start in any state.  It's renumbered to state 0
+// to suit Flib, and the other states are numbered in order.  They can have
+// at most MAX_STATES states.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{make_from_chromosome, state_to_char, Flib, MAX_STATES};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Json,
+    Hoa,
+    Kiss2,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "json" => Ok(Format::Json),
+            "hoa" => Ok(Format::Hoa),
+            "kiss2" => Ok(Format::Kiss2),
+            _ => Err(format!(
+                "unknown format '{}'; expected json, hoa or kiss2",
+                s
+            )),
+        }
+    }
+}
+
+impl Format {
+    // Guess a file's format from its extension.
+    pub fn from_path(path: &Path) -> Option<Format> {
+        match path.extension().and_then(OsStr::to_str) {
+            Some("json") => Some(Format::Json),
+            Some("hoa") => Some(Format::Hoa),
+            Some("kiss") | Some("kiss2") => Some(Format::Kiss2),
+            _ => None,
+        }
+    }
+}
+
+pub fn export(flib: &Flib, format: Format) -> String {
+    match format {
+        Format::Json => export_json(flib),
+        Format::Hoa => export_hoa(flib),
+        Format::Kiss2 => export_kiss2(flib),
+    }
+}
+
+pub fn import(text: &str, format: Format) -> Result<Flib, String> {
+    match format {
+        Format::Json => import_json(text),
+        Format::Hoa => import_hoa(text),
+        Format::Kiss2 => import_kiss2(text),
+    }
+}
+
+// Transitions being collected by an importer, as (output, destination)
+// for each state and input, with states numbered as in the file.
+struct Table {
+    initial: usize,
+    transitions: Vec<[Option<(char, usize)>; 2]>,
+}
+
+impl Table {
+    fn new(num_states: usize, initial: usize) -> Result<Table, String> {
+        if num_states == 0 {
+            return Err(String::from("the machine has no states"));
+        }
+        if num_states > MAX_STATES {
+            return Err(format!(
+                "the machine has {} states, but a flib can have at most {}",
+                num_states, MAX_STATES
+            ));
+        }
+        Ok(Table {
+            initial,
+            transitions: vec![[None, None]; num_states],
+        })
+    }
+
+    fn add(&mut self, from: usize, input: char, output: char, to: usize) -> Result<(), String> {
+        let num_states = self.transitions.len();
+        if from >= num_states || to >= num_states {
+            return Err(format!(
+                "transition {} -> {} refers to a state past {}",
+                from,
+                to,
+                num_states - 1
+            ));
+        }
+        let input = match input {
+            '0' => 0,
+            '1' => 1,
+            _ => return Err(format!("invalid input '{}'", input)),
+        };
+        if output != '0' && output != '1' {
+            return Err(format!("invalid output '{}'", output));
+        }
+        let slot = &mut self.transitions[from][input];
+        if slot.is_some() {
+            return Err(format!(
+                "state {} has more than one transition on input {}",
+                from, input
+            ));
+        }
+        *slot = Some((output, to));
+        Ok(())
+    }
+
+    // Build the flib, swapping the initial state with state 0.
+    fn into_flib(self) -> Result<Flib, String> {
+        if self.initial >= self.transitions.len() {
+            return Err(format!("initial state {} doesn't exist", self.initial));
+        }
+        let renumber = |state: usize| match state {
+            s if s == self.initial => 0,
+            0 => self.initial,
+            s => s,
+        };
+        let mut chromosome = String::new();
+        for state in 0..self.transitions.len() {
+            for (input, transition) in self.transitions[renumber(state)].iter().enumerate() {
+                let (output, to) = transition.ok_or_else(|| {
+                    format!(
+                        "state {} has no transition on input {}",
+                        renumber(state),
+                        input
+                    )
+                })?;
+                chromosome.push(output);
+                chromosome.push(state_to_char(renumber(to)));
+            }
+        }
+        Ok(make_from_chromosome(chromosome))
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct JsonMachine {
+    states: usize,
+    initial: usize,
+    transitions: Vec<JsonTransition>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+struct JsonTransition {
+    from: usize,
+    input: char,
+    output: char,
+    to: usize,
+}
+
+fn export_json(flib: &Flib) -> String {
+    let mut transitions = vec![];
+    for (from, state) in flib.states.iter().enumerate() {
+        for (input, &(output, to)) in state.iter().enumerate() {
+            transitions.push(JsonTransition {
+                from,
+                input: if input == 0 { '0' } else { '1' },
+                output,
+                to,
+            });
+        }
+    }
+    let machine = JsonMachine {
+        states: flib.states.len(),
+        initial: 0,
+        transitions,
+    };
+    serde_json::to_string_pretty(&machine).expect("Failed to serialize flib")
+}
+
+fn import_json(text: &str) -> Result<Flib, String> {
+    let machine: JsonMachine = serde_json::from_str(text).map_err(|e| e.to_string())?;
+    let mut table = Table::new(machine.states, machine.initial)?;
+    for t in machine.transitions {
+        table.add(t.from, t.input, t.output, t.to)?;
+    }
+    table.into_flib()
+}
+
+fn export_hoa(flib: &Flib) -> String {
+    let mut hoa = String::from("HOA: v1\n");
+    hoa.push_str(&format!("name: \"flib {}\"\n", flib.as_chromosome()));
+    hoa.push_str(&format!("States: {}\n", flib.states.len()));
+    hoa.push_str("Start: 0\n");
+    hoa.push_str("AP: 2 \"i\" \"o\"\n");
+    hoa.push_str("controllable-AP: 1\n");
+    hoa.push_str("acc-name: all\n");
+    hoa.push_str("Acceptance: 0 t\n");
+    hoa.push_str("--BODY--\n");
+    for (from, state) in flib.states.iter().enumerate() {
+        hoa.push_str(&format!("State: {}\n", from));
+        for (input, &(output, to)) in state.iter().enumerate() {
+            let i = if input == 0 { "!0" } else { "0" };
+            let o = if output == '1' { "1" } else { "!1" };
+            hoa.push_str(&format!("[{}&{}] {}\n", i, o, to));
+        }
+    }
+    hoa.push_str("--END--\n");
+    hoa
+}
+
+// Find the numbers of the "i" and "o" propositions in the value of an AP
+// header, such as `2 "i" "o"`.
+fn hoa_aps(value: &str) -> Result<(usize, usize), String> {
+    let invalid = || format!("invalid AP line 'AP:{}'", value);
+    let (count, names) = value
+        .trim()
+        .split_once(char::is_whitespace)
+        .unwrap_or((value.trim(), ""));
+    let count: usize = count.parse().map_err(|_| invalid())?;
+    // Names are the quoted strings, so splitting on quotes leaves them at
+    // odd positions, with only spaces around them.
+    let pieces: Vec<&str> = names.split('"').collect();
+    if pieces.len().is_multiple_of(2) || pieces.iter().step_by(2).any(|p| !p.trim().is_empty()) {
+        return Err(invalid());
+    }
+    let names: Vec<&str> = pieces.iter().skip(1).step_by(2).copied().collect();
+    if names.len() != count {
+        return Err(invalid());
+    }
+    let find = |name| names.iter().position(|&n| n == name);
+    match (find("i"), find("o")) {
+        (Some(input), Some(output)) => Ok((input, output)),
+        _ => Err(String::from("expected atomic propositions \"i\" and \"o\"")),
+    }
+}
+
+// Read one literal of an edge label, such as "!0", returning the
+// proposition's number and value.
+fn hoa_literal(literal: &str) -> Result<(usize, char), String> {
+    let literal = literal.trim();
+    let (value, number) = match literal.strip_prefix('!') {
+        Some(rest) => ('0', rest.trim()),
+        None => ('1', literal),
+    };
+    let number = number
+        .parse()
+        .map_err(|_| format!("unsupported edge label literal '{}'", literal))?;
+    Ok((number, value))
+}
+
+fn import_hoa(text: &str) -> Result<Flib, String> {
+    let (header, body) = text.split_once("--BODY--").ok_or("missing --BODY-- line")?;
+    let body = body.split("--END--").next().unwrap_or("");
+
+    let mut num_states = None;
+    let mut initial = 0;
+    let mut aps = None;
+    for line in header.lines() {
+        if let Some(value) = line.strip_prefix("States:") {
+            num_states = Some(value.trim().parse().map_err(|_| "invalid States line")?);
+        } else if let Some(value) = line.strip_prefix("Start:") {
+            initial = value.trim().parse().map_err(|_| "invalid Start line")?;
+        } else if let Some(value) = line.strip_prefix("AP:") {
+            aps = Some(hoa_aps(value)?);
+        }
+    }
+    let (input_ap, output_ap) = aps.ok_or("missing AP line")?;
+    let mut table = Table::new(num_states.ok_or("missing States line")?, initial)?;
+
+    let mut from = None;
+    for line in body.lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if let Some(value) = line.strip_prefix("State:") {
+            let number = value.split_whitespace().next().unwrap_or("");
+            from = Some(
+                number
+                    .parse()
+                    .map_err(|_| format!("invalid line '{}'", line))?,
+            );
+            continue;
+        }
+        let from = from.ok_or("edge before the first State line")?;
+        let rest = line
+            .strip_prefix('[')
+            .ok_or_else(|| format!("unsupported edge '{}'", line))?;
+        let (label, to) = rest
+            .split_once(']')
+            .ok_or_else(|| format!("invalid edge '{}'", line))?;
+        let to = to
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid edge '{}'", line))?;
+        let mut input = None;
+        let mut output = None;
+        for literal in label.split('&') {
+            match hoa_literal(literal)? {
+                (ap, value) if ap == input_ap => input = Some(value),
+                (ap, value) if ap == output_ap => output = Some(value),
+                _ => return Err(format!("unknown proposition in '{}'", line)),
+            }
+        }
+        match (input, output) {
+            (Some(input), Some(output)) => table.add(from, input, output, to)?,
+            _ => return Err(format!("edge '{}' must fix both i and o", line)),
+        }
+    }
+    table.into_flib()
+}
+
+fn export_kiss2(flib: &Flib) -> String {
+    let mut kiss = format!(
+        ".i 1\n.o 1\n.p {}\n.s {}\n.r s0\n",
+        flib.states.len() * 2,
+        flib.states.len()
+    );
+    for (from, state) in flib.states.iter().enumerate() {
+        for (input, &(output, to)) in state.iter().enumerate() {
+            kiss.push_str(&format!("{} s{} s{} {}\n", input, from, to, output));
+        }
+    }
+    kiss.push_str(".e\n");
+    kiss
+}
+
+fn import_kiss2(text: &str) -> Result<Flib, String> {
+    let mut reset = None;
+    let mut rows = vec![];
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        match fields[..] {
+            [".i", n] | [".o", n] if n != "1" => {
+                return Err(format!(
+                    "only one input and output bit are supported, not {}",
+                    n
+                ))
+            }
+            [".r", state] => reset = Some(state),
+            [".e"] | [".end"] => break,
+            [directive, ..] if directive.starts_with('.') => {}
+            [input, from, to, output] => rows.push((input, from, to, output)),
+            _ => return Err(format!("invalid line '{}'", line)),
+        }
+    }
+
+    // Number the states in the order their rows first appear, starting
+    // from the reset state if there is one.  A state that only appears as
+    // a destination has no transitions, which into_flib() reports.
+    let mut numbers: HashMap<&str, usize> = HashMap::new();
+    let names = reset
+        .into_iter()
+        .chain(rows.iter().map(|&(_, from, _, _)| from))
+        .chain(rows.iter().map(|&(_, _, to, _)| to));
+    for name in names {
+        let next = numbers.len();
+        numbers.entry(name).or_insert(next);
+    }
+
+    let mut table = Table::new(numbers.len(), 0)?;
+    for (input, from, to, output) in rows {
+        let output = match output {
+            "0" | "1" => output.chars().next().unwrap(),
+            _ => return Err(format!("invalid output '{}'", output)),
+        };
+        // A don't-care input covers both.
+        let inputs: &[char] = match input {
+            "0" => &['0'],
+            "1" => &['1'],
+            "-" => &['0', '1'],
+            _ => return Err(format!("invalid input '{}'", input)),
+        };
+        for &input in inputs {
+            table.add(numbers[from], input, output, numbers[to])?;
+        }
+    }
+    table.into_flib()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng, StdRng};
+
+    const FORMATS: [Format; 3] = [Format::Json, Format::Hoa, Format::Kiss2];
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = StdRng::from_seed(&[37][..]);
+        for _ in 0..20 {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(rng.gen_range(1, 10), &mut rng);
+            for &format in &FORMATS {
+                let imported = import(&export(&flib, format), format).unwrap();
+                assert_eq!(imported.as_chromosome(), flib.as_chromosome());
+            }
+        }
+    }
+
+    #[test]
+    fn test_export_hoa() {
+        let flib = make_from_chromosome(String::from("0B1A"));
+        let hoa = export(&flib, Format::Hoa);
+        assert!(hoa.starts_with("HOA: v1\n"));
+        assert!(hoa.contains("--BODY--\nState: 0\n[!0&!1] 1\n[0&1] 0\n--END--\n"));
+    }
+
+    #[test]
+    fn test_import_renumbers_start_state() {
+        // Start in state 1 of the file, which has its own state names.
+        let kiss = ".i 1\n.o 1\n.r b\n0 a a 1\n1 a b 0\n- b a 1\n.e\n";
+        assert_eq!(
+            import(kiss, Format::Kiss2).unwrap().as_chromosome(),
+            "1B1B1B0A"
+        );
+
+        let hoa = "HOA: v1\nStates: 2\nStart: 1\nAP: 2 \"i\" \"o\"\n--BODY--\n\
+                   State: 0\n[!0 & 1] 0\n[0&!1] 1\n\
+                   State: 1\n[!0&!1] 0\n[0&1] 1\n--END--\n";
+        assert_eq!(
+            import(hoa, Format::Hoa).unwrap().as_chromosome(),
+            "0B1A1B0A"
+        );
+    }
+
+    #[test]
+    fn test_import_hoa_by_ap_name() {
+        // The output is proposition 0 here, and the input proposition 1.
+        let hoa = "HOA: v1\nStates: 1\nStart: 0\nAP: 2 \"o\" \"i\"\n--BODY--\n\
+                   State: 0\n[0&!1] 0\n[!0&1] 0\n--END--\n";
+        assert_eq!(import(hoa, Format::Hoa).unwrap().as_chromosome(), "1A0A");
+
+        let unnamed = hoa.replace("\"o\" \"i\"", "\"x\" \"i\"");
+        assert!(import(&unnamed, Format::Hoa).unwrap_err().contains("\"o\""));
+        let miscounted = hoa.replace("AP: 2", "AP: 3");
+        assert!(import(&miscounted, Format::Hoa)
+            .unwrap_err()
+            .contains("invalid AP"));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let missing = r#"{"states": 1, "initial": 0,
+            "transitions": [{"from": 0, "input": "0", "output": "1", "to": 0}]}"#;
+        assert_eq!(
+            import(missing, Format::Json).unwrap_err(),
+            "state 0 has no transition on input 1"
+        );
+        let kiss = "0 a a 1\n0 a a 0\n1 a a 1\n";
+        assert!(import(kiss, Format::Kiss2)
+            .unwrap_err()
+            .contains("more than one transition"));
+        assert!(import("HOA: v1\nStates: 1\n", Format::Hoa).is_err());
+        assert!(import(".i 2\n", Format::Kiss2).is_err());
+
+        let empty = r#"{"states": 0, "initial": 0, "transitions": []}"#;
+        assert_eq!(
+            import(empty, Format::Json).unwrap_err(),
+            "the machine has no states"
+        );
+        let huge = r#"{"states": 27, "initial": 0, "transitions": []}"#;
+        assert!(import(huge, Format::Json)
+            .unwrap_err()
+            .contains("at most 26"));
+        assert_eq!(
+            import(".i 1\n.o 1\n.e\n", Format::Kiss2).unwrap_err(),
+            "the machine has no states"
+        );
+    }
+}
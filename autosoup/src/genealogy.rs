@@ -0,0 +1,229 @@
+// Genealogy: record where every flib in a run came from, so that the
+// ancestry of the winner can be traced back to the initial population.
+//
+// Genealogy is an Observer that keeps every birth simulate() reports.  The
+// ancestry of one flib can then be exported as a Graphviz DOT graph, with
+// an edge from each parent to its child, or as JSON.
+
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+use serde::Serialize;
+
+use crate::{Birth, Control, Observer, Origin, Report};
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct Individual {
+    pub id: usize,
+    pub generation: usize,
+    pub parents: Vec<usize>,
+    pub origin: Origin,
+    pub chromosome: String,
+    // Score when the flib was first evaluated.
+    pub fitness: f32,
+}
+
+#[derive(Debug, Default)]
+pub struct Genealogy {
+    // Indexed by ID.
+    individuals: Vec<Individual>,
+}
+
+impl Observer for Genealogy {
+    fn generation(&mut self, _report: &Report) -> Control {
+        Control::Continue
+    }
+
+    fn birth(&mut self, birth: &Birth) {
+        assert_eq!(birth.id, self.individuals.len(), "births out of order");
+        self.individuals.push(Individual {
+            id: birth.id,
+            generation: birth.generation,
+            parents: birth.parents.to_vec(),
+            origin: birth.origin,
+            chromosome: birth.chromosome.to_string(),
+            fitness: birth.fitness,
+        });
+    }
+}
+
+#[derive(Serialize)]
+struct Lineage<'a> {
+    environment: &'a str,
+    id: usize,
+    ancestors: Vec<&'a Individual>,
+}
+
+impl Genealogy {
+    pub fn new() -> Genealogy {
+        Genealogy::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.individuals.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.individuals.is_empty()
+    }
+
+    pub fn get(&self, id: usize) -> Option<&Individual> {
+        self.individuals.get(id)
+    }
+
+    // The flib with this ID and all of its ancestors, oldest first.
+    pub fn ancestry(&self, id: usize) -> Vec<&Individual> {
+        let mut seen = BTreeSet::new();
+        let mut stack = vec![id];
+        while let Some(id) = stack.pop() {
+            if seen.insert(id) {
+                stack.extend(&self.individuals[id].parents);
+            }
+        }
+        seen.into_iter().map(|id| &self.individuals[id]).collect()
+    }
+
+    // The ancestry of `id` as a DOT digraph.  The flib itself is drawn in
+    // bold, and each node shows the generation a flib was born in, how it
+    // was made and its fitness at birth.
+    pub fn to_dot(&self, id: usize, name: &str) -> String {
+        let mut dot = format!("digraph \"{}\" {{\n    node [shape=box];\n", name);
+        let ancestors = self.ancestry(id);
+        for individual in &ancestors {
+            writeln!(
+                dot,
+                "    n{} [label=\"#{} gen {}\\n{}\\n{} {:.4}\"{}];",
+                individual.id,
+                individual.id,
+                individual.generation,
+                individual.chromosome,
+                individual.origin,
+                individual.fitness,
+                if individual.id == id {
+                    ", style=bold"
+                } else {
+                    ""
+                }
+            )
+            .unwrap();
+        }
+        for individual in &ancestors {
+            for parent in &individual.parents {
+                writeln!(dot, "    n{} -> n{};", parent, individual.id).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+
+    // The ancestry of `id` as a JSON object, with the environment it was
+    // evolved for.
+    pub fn to_json(&self, id: usize, environment: &str) -> serde_json::Value {
+        serde_json::to_value(Lineage {
+            environment,
+            id,
+            ancestors: self.ancestry(id),
+        })
+        .expect("Failed to serialize lineage")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{simulate, Crossover, Replacement, SimParams};
+    use rand::{SeedableRng, StdRng};
+
+    fn birth(genealogy: &mut Genealogy, id: usize, parents: &[usize], origin: Origin) {
+        genealogy.birth(&Birth {
+            id,
+            generation: id,
+            parents,
+            origin,
+            chromosome: "0A1A",
+            fitness: 0.5,
+        });
+    }
+
+    fn example() -> Genealogy {
+        let mut genealogy = Genealogy::new();
+        birth(&mut genealogy, 0, &[], Origin::Initial);
+        birth(&mut genealogy, 1, &[], Origin::Initial);
+        birth(&mut genealogy, 2, &[], Origin::Initial);
+        birth(
+            &mut genealogy,
+            3,
+            &[0, 1],
+            Origin::Crossover(Crossover::OnePoint),
+        );
+        birth(&mut genealogy, 4, &[3], Origin::Mutation);
+        genealogy
+    }
+
+    #[test]
+    fn test_ancestry() {
+        let genealogy = example();
+        let ids: Vec<usize> = genealogy.ancestry(4).iter().map(|i| i.id).collect();
+        assert_eq!(ids, [0, 1, 3, 4]);
+        assert_eq!(genealogy.ancestry(2).len(), 1);
+    }
+
+    #[test]
+    fn test_exports() {
+        let genealogy = example();
+        let dot = genealogy.to_dot(4, "011001");
+        assert!(dot.starts_with("digraph \"011001\" {\n"));
+        assert!(dot.contains("    n3 [label=\"#3 gen 3\\n0A1A\\ncrossover:one-point 0.5000\"];\n"));
+        assert!(dot.contains("    n4 [label=\"#4 gen 4\\n0A1A\\nmutation 0.5000\", style=bold];\n"));
+        assert!(dot.contains("    n0 -> n3;\n    n1 -> n3;\n    n3 -> n4;\n"));
+        assert!(!dot.contains("n2"));
+
+        let json = genealogy.to_json(4, "011001");
+        assert_eq!(json["id"], 4);
+        assert_eq!(json["ancestors"].as_array().unwrap().len(), 4);
+        assert_eq!(json["ancestors"][2]["origin"], "crossover:one-point");
+        assert_eq!(json["ancestors"][2]["parents"], serde_json::json!([0, 1]));
+    }
+
+    #[test]
+    fn test_simulate_births() {
+        for &replacement in &[
+            Replacement::SteadyState,
+            Replacement::Generational,
+            Replacement::Comma(15),
+            Replacement::Plus(5),
+        ] {
+            check_births(SimParams {
+                replacement,
+                max_generations: Some(2000),
+                ..SimParams::default()
+            });
+        }
+    }
+
+    fn check_births(params: SimParams) {
+        let mut rng = StdRng::from_seed(&[42][..]);
+        let mut genealogy = Genealogy::new();
+        let outcome = simulate(&params, &mut rng, &mut genealogy);
+        let winner = genealogy.get(outcome.best_id.unwrap()).unwrap();
+        assert_eq!(winner.chromosome, outcome.best);
+        assert_eq!(winner.fitness, outcome.best_score);
+
+        // Every lineage goes back to the initial population, and parents
+        // are always older than their children.
+        let ancestry = genealogy.ancestry(winner.id);
+        assert!(ancestry
+            .iter()
+            .all(|i| (i.origin == Origin::Initial) == (i.id < params.population_size)));
+        for individual in &ancestry {
+            assert!(individual.parents.iter().all(|&p| p < individual.id));
+            let parents = match individual.origin {
+                Origin::Initial => 0..=0,
+                Origin::Mutation => 1..=1,
+                // Both parents can be the same flib.
+                Origin::Crossover(_) | Origin::CrossoverMutation(_) => 1..=2,
+            };
+            assert!(parents.contains(&individual.parents.len()));
+        }
+    }
+}
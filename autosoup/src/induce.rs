@@ -0,0 +1,160 @@
+// Supervised transducer induction: evolve a flib that maps logged inputs
+// to logged outputs, such as a small protocol state machine.
+//
+// A trace file has one (input, expected output) pair per line, as two
+// equal-length strings of 0s and 1s separated by whitespace.  Blank lines
+// and lines starting with '#' are ignored:
+//
+//     # request bits, ack bits
+//     0110  0100
+//     111   101
+//
+// The flib starts each trace afresh from state 0, and its fitness is the
+// fraction of output symbols it gets right over all of the traces.
+
+use std::fs;
+use std::path::Path;
+
+use rand::Rng;
+
+use crate::variants::{evolve_with, Kind};
+use crate::{make_from_chromosome, Flib, Outcome, SimParams};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trace {
+    pub input: String,
+    pub output: String,
+}
+
+pub fn parse_traces(text: &str) -> Result<Vec<Trace>, String> {
+    let mut traces = vec![];
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let trace = match fields[..] {
+            [input, output] => Trace {
+                input: input.to_string(),
+                output: output.to_string(),
+            },
+            _ => {
+                return Err(format!(
+                    "line {}: expected an input and an output sequence",
+                    number + 1
+                ))
+            }
+        };
+        if !is_binary(&trace.input) || !is_binary(&trace.output) {
+            return Err(format!("line {}: sequences must be 0s and 1s", number + 1));
+        }
+        if trace.input.len() != trace.output.len() {
+            return Err(format!(
+                "line {}: input has {} symbols but output has {}",
+                number + 1,
+                trace.input.len(),
+                trace.output.len()
+            ));
+        }
+        traces.push(trace);
+    }
+    if traces.is_empty() {
+        return Err(String::from("no traces found"));
+    }
+    Ok(traces)
+}
+
+pub fn load_traces(path: &Path) -> Result<Vec<Trace>, String> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| format!("can't read trace file {}: {}", path.display(), e))?;
+    parse_traces(&text).map_err(|e| format!("invalid trace file {}: {}", path.display(), e))
+}
+
+fn is_binary(s: &str) -> bool {
+    s.chars().all(|ch| ch == '0' || ch == '1')
+}
+
+// Per-symbol accuracy of `flib` over all of the traces.
+pub fn accuracy(flib: &mut Flib, traces: &[Trace]) -> f32 {
+    let mut matches = 0;
+    let mut total = 0;
+    for trace in traces {
+        let produced = flib.transduce(&trace.input);
+        matches += produced
+            .chars()
+            .zip(trace.output.chars())
+            .filter(|(a, b)| a == b)
+            .count();
+        total += trace.output.len();
+    }
+    (matches as f32) / (total as f32)
+}
+
+// Evolve a flib that reproduces the traces.  params.environment is unused;
+// everything else controls the run as it does for simulate().
+pub fn induce<R: Rng>(traces: &[Trace], params: &SimParams, rng: &mut R) -> Outcome {
+    let fitness = |chromosome: &str| {
+        let mut flib = make_from_chromosome(chromosome.to_string());
+        accuracy(&mut flib, traces)
+    };
+    evolve_with(Kind::Mealy, params, fitness, rng)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    // Parity: output 1 once an odd number of 1s has been seen.
+    const PARITY: &str = "0A1B1B0A";
+
+    #[test]
+    fn test_parse_traces() {
+        let traces = parse_traces("# comment\n\n0110  0100\n 111\t101 \n").unwrap();
+        assert_eq!(traces.len(), 2);
+        assert_eq!(traces[1].input, "111");
+        assert_eq!(traces[1].output, "101");
+
+        assert_eq!(
+            parse_traces("01 01\n011 01\n").unwrap_err(),
+            "line 2: input has 3 symbols but output has 2"
+        );
+        assert!(parse_traces("01 0x").unwrap_err().contains("0s and 1s"));
+        assert!(parse_traces("01").unwrap_err().contains("line 1"));
+        assert_eq!(parse_traces("# nothing\n").unwrap_err(), "no traces found");
+    }
+
+    #[test]
+    fn test_accuracy() {
+        let traces = parse_traces("0110 0100\n111 101\n").unwrap();
+        let mut parity = make_from_chromosome(String::from(PARITY));
+        assert_eq!(parity.transduce("0110"), "0100");
+        assert_eq!(accuracy(&mut parity, &traces), 1.0);
+        // An echo matches 5 of the 7 output symbols.
+        let mut echo = make_from_chromosome(String::from("0A1A"));
+        assert_eq!(accuracy(&mut echo, &traces), 5.0 / 7.0);
+    }
+
+    #[test]
+    fn test_induce_parity() {
+        let mut parity = make_from_chromosome(String::from(PARITY));
+        let traces: Vec<Trace> = ["0110", "1011", "0001", "1111", "0100", "1101001"]
+            .iter()
+            .map(|input| Trace {
+                input: input.to_string(),
+                output: parity.transduce(input),
+            })
+            .collect();
+        let params = SimParams {
+            num_states: 2,
+            max_generations: Some(20000),
+            ..SimParams::default()
+        };
+        let mut rng = StdRng::from_seed(&[35][..]);
+        let solution = induce(&traces, &params, &mut rng)
+            .solution
+            .expect("no transducer found");
+        assert_eq!(accuracy(&mut make_from_chromosome(solution), &traces), 1.0);
+    }
+}
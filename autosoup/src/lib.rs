@@ -0,0 +1,1493 @@
+use cache::{CacheStats, FitnessCache};
+use local_search::Annealing;
+use rand::{Rng, SeedableRng, StdRng};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io;
+use std::iter::Iterator;
+use std::str::FromStr;
+
+pub mod batch;
+pub mod cache;
+pub mod cli;
+pub mod codegen;
+pub mod coevolve;
+pub mod compiled;
+pub mod config;
+pub mod dashboard;
+pub mod experiment;
+pub mod formats;
+pub mod genealogy;
+pub mod induce;
+pub mod local_search;
+pub mod output;
+pub mod pareto;
+#[cfg(test)]
+mod properties;
+pub mod serve;
+pub mod significance;
+pub mod tune;
+pub mod variants;
+
+// Chromosomes name states with the letters A-Z, so no flib can have more
+// states than this.
+pub const MAX_STATES: usize = 26;
+
+// A random number generator that always gives the same numbers for `seed`.
+pub fn seeded_rng(seed: u64) -> StdRng {
+    StdRng::from_seed(&[seed as usize][..])
+}
+
+// A generator seeded with `seed`, or from the operating system when
+// there's no seed.
+pub fn make_rng(seed: Option<u64>) -> io::Result<StdRng> {
+    match seed {
+        Some(seed) => Ok(seeded_rng(seed)),
+        None => StdRng::new(),
+    }
+}
+
+// Convert the integer number `state` into the matching character; for example, 1 maps to 'B'.
+pub fn state_to_char(state: usize) -> char {
+    (b'A' + (state as u8)) as char
+}
+
+// Convert a character `char` such as 'B' into a state # like 1.
+pub fn char_to_state(ch: char) -> usize {
+    ((ch as u8) - b'A') as usize
+}
+
+// Structure representing a single automaton.
+#[derive(Debug)]
+pub struct Flib {
+    num_states: usize,
+    current_state: usize,
+    // Each state's transition table is an input symbol, and the
+    // number of the new state to transition to.
+    states: Vec<Vec<(char, usize)>>,
+}
+
+impl Flib {
+    // Consume the input character `char`, outputting a response character and
+    // updating the flib's internal state according to its transition table.
+    pub fn transition(&mut self, input: char) -> char {
+        // Look for matching character in the state transition table.
+        // input should end up either 0 or 1.
+        let input = (input as usize) - ('0' as usize);
+        let (output, dest_state) = self.states[self.current_state][input];
+        self.current_state = dest_state;
+        output
+    }
+
+    // Run the Flib from its starting state over `inputs`, returning its
+    // outputs.
+    pub fn transduce(&mut self, inputs: &str) -> String {
+        self.current_state = 0;
+        inputs.chars().map(|ch| self.transition(ch)).collect()
+    }
+
+    // Given an environment string, initialize the Flib to its starting state and then
+    // assess how well it predicted the environment.  Returns a floating-point number
+    // that's the ratio of correct predictions: 1.00 would be a perfect predictor, and 0.00
+    // would be a perfect anti-predictor.
+    pub fn predict(&mut self, environment: &str) -> f32 {
+        self.predict_horizon(environment, Horizon::Ahead(1))
+    }
+
+    // Like predict(), but with each output judged against the symbols
+    // `horizon` describes.
+    pub fn predict_horizon(&mut self, environment: &str, horizon: Horizon) -> f32 {
+        // Feed two copies of the environment into the Flib.  The expected
+        // symbols wrap around, so the symbol k steps after position i is
+        // at (i + k) % n.
+        let env: Vec<char> = environment.chars().collect();
+        let n = env.len();
+        let mut matches = 0;
+        self.current_state = 0;
+        for i in 0..2 * n {
+            let prediction = self.transition(env[i % n]);
+            match horizon {
+                Horizon::Ahead(k) => {
+                    if prediction == env[(i + k) % n] {
+                        matches += 1;
+                    }
+                }
+                Horizon::Window(w) => matches += self.roll_out(prediction, &env, i, w),
+            }
+        }
+
+        let per_step = match horizon {
+            Horizon::Ahead(_) => 1,
+            Horizon::Window(w) => w,
+        };
+        (matches as f32) / ((n * 2 * per_step) as f32)
+    }
+
+    // Having made `prediction` after position `i`, keep feeding the Flib
+    // its own predictions to forecast `window` symbols in all, and count
+    // how many match.  The Flib's state is left as it was.
+    fn roll_out(&mut self, mut prediction: char, env: &[char], i: usize, window: usize) -> usize {
+        let n = env.len();
+        let saved_state = self.current_state;
+        let mut matches = 0;
+        for k in 1..=window {
+            if prediction == env[(i + k) % n] {
+                matches += 1;
+            }
+            // An output that isn't a valid input ends the forecast.
+            if k == window || (prediction != '0' && prediction != '1') {
+                break;
+            }
+            prediction = self.transition(prediction);
+        }
+        self.current_state = saved_state;
+        matches
+    }
+
+    // Return a string representation of the Flib's transition table.
+    pub fn as_chromosome(&self) -> String {
+        let mut c = String::from("");
+        for state in &self.states {
+            for transition in state {
+                c.push(transition.0);
+                c.push(state_to_char(transition.1));
+            }
+        }
+        c
+    }
+
+    // Replace the Flib's transition table with the table given by the specified chromosome string.
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_chromosome(&mut self, chromosome: String) {
+        self.current_state = 0;
+        // Each state occupies 4 characters, so the total number of
+        // states is just the length divided by 4.
+        self.num_states = (chromosome.chars().count()) / 4;
+
+        // Fill out the state vectors
+        self.states = vec![];
+
+        let mut it = chromosome.chars();
+
+        for _i in 0..self.num_states {
+            // Get four characters from the iterator
+            let output0 = it.next().expect("first character missing");
+            let dest0 = it.next().expect("second character missing");
+            let output1 = it.next().expect("third character missing");
+            let dest1 = it.next().expect("fourth character missing");
+
+            let transition = vec![
+                (output0, char_to_state(dest0)),
+                (output1, char_to_state(dest1)),
+            ];
+            self.states.push(transition);
+        }
+    }
+
+    // Count the states that can be reached from the starting state on some
+    // input sequence.  Unreachable states never affect the flib's output.
+    pub fn reachable_states(&self) -> usize {
+        let mut seen = vec![false; self.states.len()];
+        let mut stack = vec![0];
+        while let Some(state) = stack.pop() {
+            if seen[state] {
+                continue;
+            }
+            seen[state] = true;
+            for &(_, dest) in &self.states[state] {
+                stack.push(dest);
+            }
+        }
+        seen.iter().filter(|&&reached| reached).count()
+    }
+
+    // A chromosome for the same machine with its states renumbered in the
+    // order a breadth-first search from the starting state finds them, and
+    // unreachable states left out.  Flibs that only differ in those ways
+    // behave identically and have the same canonical chromosome.
+    pub fn canonical_chromosome(&self) -> String {
+        let mut numbers = vec![None; self.states.len()];
+        numbers[0] = Some(0);
+        let mut order = vec![0];
+        let mut next = 0;
+        while next < order.len() {
+            for &(_, dest) in &self.states[order[next]] {
+                if numbers[dest].is_none() {
+                    numbers[dest] = Some(order.len());
+                    order.push(dest);
+                }
+            }
+            next += 1;
+        }
+        let mut chromosome = String::with_capacity(order.len() * 4);
+        for &state in &order {
+            for &(output, dest) in &self.states[state] {
+                chromosome.push(output);
+                chromosome.push(state_to_char(numbers[dest].unwrap()));
+            }
+        }
+        chromosome
+    }
+
+    // XXX It would be nice to make this a class method.
+    pub fn randomize<R: Rng>(&mut self, num_states: usize, rng: &mut R) {
+        // Create a random set of state transitions
+        // XXX could write this to produce a string and then use make_from_chromosome()
+        self.num_states = num_states;
+        self.current_state = 0;
+        self.states = vec![];
+
+        for _i in 0..num_states {
+            let mut new_state = vec![];
+            for _j in 0..2 {
+                new_state.push((
+                    (b'0' + rng.gen_range(0, 2)) as char,
+                    rng.gen_range(0, num_states),
+                ));
+            }
+            self.states.push(new_state);
+        }
+    }
+}
+
+pub fn make_from_chromosome(chromosome: String) -> Flib {
+    let mut baby = Flib {
+        num_states: 0,
+        current_state: 0,
+        states: vec![],
+    };
+    baby.from_chromosome(chromosome);
+    baby
+}
+
+// Like make_from_chromosome(), but for chromosomes from outside the
+// program: anything that isn't a well-formed chromosome is an error rather
+// than a panic or a Flib that fails later.
+pub fn parse_chromosome(chromosome: &str) -> Result<Flib, String> {
+    let chars: Vec<char> = chromosome.chars().collect();
+    if chars.is_empty() || !chars.len().is_multiple_of(4) {
+        return Err(format!(
+            "chromosome '{}' must be a non-empty multiple of 4 characters long",
+            chromosome
+        ));
+    }
+    let num_states = chars.len() / 4;
+    for (i, &ch) in chars.iter().enumerate() {
+        let valid = if i % 2 == 0 {
+            ch == '0' || ch == '1'
+        } else {
+            ch.is_ascii_uppercase() && char_to_state(ch) < num_states
+        };
+        if !valid {
+            return Err(format!(
+                "chromosome '{}' has an invalid character '{}' at position {}",
+                chromosome, ch, i
+            ));
+        }
+    }
+    Ok(make_from_chromosome(chromosome.to_string()))
+}
+
+fn output_population(heading: String, population: &[Flib]) {
+    println!("{}", heading);
+    for flib in population {
+        println!("{:?}", flib.as_chromosome());
+    }
+    println!();
+}
+
+// Evaluate the entire population on how well they predict the environment
+pub fn score_population(population: &mut [Flib], environment: &str, horizon: Horizon) -> Vec<f32> {
+    let mut scores: Vec<f32> = vec![];
+    for flib in population {
+        scores.push(flib.predict_horizon(environment, horizon));
+    }
+
+    scores
+}
+
+// Mean pairwise Hamming distance between equal-length strings, as a
+// fraction of their length; 0.0 means they're all identical.
+//
+// Rather than compare every pair, this counts the symbols at each
+// position: two strings match there only if they have the same symbol, so
+// the number of differing pairs follows from the counts, in time linear
+// in the number of strings.
+pub fn diversity(strings: &[String]) -> f32 {
+    let n = strings.len();
+    let length = strings.first().map_or(0, |s| s.len());
+    let num_pairs = n * n.saturating_sub(1) / 2;
+    if num_pairs * length == 0 {
+        return 0.0;
+    }
+    let mut total = 0;
+    let mut counts = [0usize; 256];
+    for position in 0..length {
+        counts.iter_mut().for_each(|c| *c = 0);
+        // A string that's too short doesn't match anything here.
+        for &symbol in strings.iter().filter_map(|s| s.as_bytes().get(position)) {
+            counts[usize::from(symbol)] += 1;
+        }
+        let matching: usize = counts.iter().map(|&c| c * c.saturating_sub(1) / 2).sum();
+        total += num_pairs - matching;
+    }
+    (total as f32) / ((num_pairs * length) as f32)
+}
+
+pub fn random_combine<R: Rng>(parent1: &str, parent2: &str, rng: &mut R) -> String {
+    let mut result = String::new();
+    let split = rng.gen_range(0, parent1.len());
+    for (i, (ch1, ch2)) in parent1.chars().zip(parent2.chars()).enumerate() {
+        if i < split {
+            result.push(ch1)
+        } else {
+            result.push(ch2);
+        }
+    }
+    result
+}
+
+// Take each character from one parent or the other with equal probability.
+pub fn uniform_combine<R: Rng>(parent1: &str, parent2: &str, rng: &mut R) -> String {
+    parent1
+        .chars()
+        .zip(parent2.chars())
+        .map(|(ch1, ch2)| if rng.gen() { ch1 } else { ch2 })
+        .collect()
+}
+
+pub fn mutate_at_index<R: Rng>(chromosome: &str, random_position: usize, rng: &mut R) -> String {
+    let mut new_chromosome = String::new();
+
+    for (i, ch) in chromosome.chars().enumerate() {
+        if i != random_position {
+            new_chromosome.push(ch);
+        } else if (i % 2) == 0 {
+            // If we're mutating an even index, we'll flip a 0 to 1 or vice versa
+            match ch {
+                '0' => new_chromosome.push('1'),
+                '1' => new_chromosome.push('0'),
+                _ => panic!("unexpected character in chromosome"),
+            }
+        } else {
+            // If we're mutating an odd index, we'll pick a new state
+            // We don't bother to check if we're picking the same
+            // existing state.
+            let num_states = chromosome.len() / 4;
+            let new_state = rng.gen_range(0, num_states);
+            new_chromosome.push(state_to_char(new_state));
+        }
+    }
+
+    new_chromosome
+}
+
+pub fn mutate<R: Rng>(chromosome: &str, rng: &mut R) -> String {
+    let random_position = rng.gen_range(0, chromosome.len());
+    mutate_at_index(chromosome, random_position, rng)
+}
+
+// How simulate() picks the parent that's crossed with the best-scoring flib.
+// The child always replaces the worst-scoring flib.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Selection {
+    // Cross the worst-scoring flib with the best-scoring one.
+    WorstBest,
+    // Cross the winners of two tournaments of the given size.
+    Tournament(usize),
+}
+
+impl FromStr for Selection {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Selection, String> {
+        match s {
+            "worst-best" => Ok(Selection::WorstBest),
+            _ => match s.strip_prefix("tournament:").map(str::parse) {
+                Some(Ok(size)) if size > 0 => Ok(Selection::Tournament(size)),
+                _ => Err(format!(
+                    "unknown selection '{}'; expected worst-best or tournament:SIZE",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+impl TryFrom<String> for Selection {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Selection, String> {
+        s.parse()
+    }
+}
+
+impl From<Selection> for String {
+    fn from(selection: Selection) -> String {
+        selection.to_string()
+    }
+}
+
+impl fmt::Display for Selection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Selection::WorstBest => write!(f, "worst-best"),
+            Selection::Tournament(size) => write!(f, "tournament:{}", size),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Crossover {
+    // Split both parents at a single random point.
+    OnePoint,
+    // Take each character from either parent at random.
+    Uniform,
+}
+
+impl FromStr for Crossover {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Crossover, String> {
+        match s {
+            "one-point" => Ok(Crossover::OnePoint),
+            "uniform" => Ok(Crossover::Uniform),
+            _ => Err(format!(
+                "unknown crossover '{}'; expected one-point or uniform",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Crossover {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Crossover, String> {
+        s.parse()
+    }
+}
+
+impl From<Crossover> for String {
+    fn from(crossover: Crossover) -> String {
+        crossover.to_string()
+    }
+}
+
+impl fmt::Display for Crossover {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Crossover::OnePoint => write!(f, "one-point"),
+            Crossover::Uniform => write!(f, "uniform"),
+        }
+    }
+}
+
+impl Crossover {
+    fn combine<R: Rng>(self, parent1: &str, parent2: &str, rng: &mut R) -> String {
+        match self {
+            Crossover::OnePoint => random_combine(parent1, parent2, rng),
+            Crossover::Uniform => uniform_combine(parent1, parent2, rng),
+        }
+    }
+}
+
+// Which future symbols a flib's outputs are judged against.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Horizon {
+    // Each output forecasts the symbol this many steps ahead; Ahead(1) is
+    // the original next-symbol prediction.
+    Ahead(usize),
+    // Each output starts a forecast of this many symbols, continued by
+    // feeding the flib its own predictions.  Scores are the fraction of
+    // all forecast symbols that were right.
+    Window(usize),
+}
+
+impl Default for Horizon {
+    fn default() -> Horizon {
+        Horizon::Ahead(1)
+    }
+}
+
+impl FromStr for Horizon {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Horizon, String> {
+        let count = |n: &str| n.parse().ok().filter(|&n: &usize| n > 0);
+        let parsed = match s.split_once(':') {
+            Some(("ahead", k)) => count(k).map(Horizon::Ahead),
+            Some(("window", w)) => count(w).map(Horizon::Window),
+            _ => None,
+        };
+        parsed.ok_or_else(|| {
+            format!(
+                "unknown horizon '{}'; expected ahead:STEPS or window:SIZE",
+                s
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for Horizon {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Horizon, String> {
+        s.parse()
+    }
+}
+
+impl From<Horizon> for String {
+    fn from(horizon: Horizon) -> String {
+        horizon.to_string()
+    }
+}
+
+impl fmt::Display for Horizon {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Horizon::Ahead(k) => write!(f, "ahead:{}", k),
+            Horizon::Window(w) => write!(f, "window:{}", w),
+        }
+    }
+}
+
+// How each generation's offspring replace the population.  In the
+// evolution-strategy schemes the population size is μ, and the count
+// given is λ, the number of offspring per generation; (μ,λ) needs λ to
+// be at least μ.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Replacement {
+    // One child replaces the worst-scoring flib, and then one random flib
+    // may be mutated; the original scheme.
+    #[default]
+    SteadyState,
+    // A whole new population of offspring replaces the old one.
+    Generational,
+    // (μ,λ): the best μ of λ offspring form the next population.
+    Comma(usize),
+    // (μ+λ): the best μ of the parents and λ offspring together.
+    Plus(usize),
+}
+
+impl FromStr for Replacement {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Replacement, String> {
+        let count = |n: &str| n.parse().ok().filter(|&n: &usize| n > 0);
+        let parsed = match s.split_once(':') {
+            Some(("comma", lambda)) => count(lambda).map(Replacement::Comma),
+            Some(("plus", lambda)) => count(lambda).map(Replacement::Plus),
+            None if s == "steady-state" => Some(Replacement::SteadyState),
+            None if s == "generational" => Some(Replacement::Generational),
+            _ => None,
+        };
+        parsed.ok_or_else(|| {
+            format!(
+                "unknown replacement '{}'; expected steady-state, generational, \
+                 comma:LAMBDA or plus:LAMBDA",
+                s
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for Replacement {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Replacement, String> {
+        s.parse()
+    }
+}
+
+impl From<Replacement> for String {
+    fn from(replacement: Replacement) -> String {
+        replacement.to_string()
+    }
+}
+
+impl fmt::Display for Replacement {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Replacement::SteadyState => write!(f, "steady-state"),
+            Replacement::Generational => write!(f, "generational"),
+            Replacement::Comma(lambda) => write!(f, "comma:{}", lambda),
+            Replacement::Plus(lambda) => write!(f, "plus:{}", lambda),
+        }
+    }
+}
+
+// Which search simulate() runs.  The single-flib searches in local_search
+// are baselines for judging what the GA's population buys.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Search {
+    #[default]
+    Genetic,
+    // Steepest-ascent hill climbing with random restarts.
+    HillClimb,
+    // Simulated annealing, as set by SimParams::annealing.
+    Anneal,
+}
+
+impl FromStr for Search {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Search, String> {
+        match s {
+            "genetic" => Ok(Search::Genetic),
+            "hill-climb" => Ok(Search::HillClimb),
+            "anneal" => Ok(Search::Anneal),
+            _ => Err(format!(
+                "unknown search '{}'; expected genetic, hill-climb or anneal",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for Search {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Search, String> {
+        s.parse()
+    }
+}
+
+impl From<Search> for String {
+    fn from(search: Search) -> String {
+        search.to_string()
+    }
+}
+
+impl fmt::Display for Search {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Search::Genetic => write!(f, "genetic"),
+            Search::HillClimb => write!(f, "hill-climb"),
+            Search::Anneal => write!(f, "anneal"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Operators {
+    pub selection: Selection,
+    pub crossover: Crossover,
+    // Chance, in each generation, of mutating one randomly chosen flib;
+    // when whole generations are bred, the chance for each offspring.
+    pub mutation_rate: f32,
+}
+
+impl Default for Operators {
+    fn default() -> Operators {
+        Operators {
+            selection: Selection::WorstBest,
+            crossover: Crossover::OnePoint,
+            mutation_rate: 1.0,
+        }
+    }
+}
+
+// Pick the best-scoring of `size` randomly chosen flibs.
+fn tournament<R: Rng>(scores: &[f32], size: usize, rng: &mut R) -> usize {
+    let mut winner = rng.gen_range(0, scores.len());
+    for _ in 1..size {
+        let challenger = rng.gen_range(0, scores.len());
+        if scores[challenger] > scores[winner] {
+            winner = challenger;
+        }
+    }
+    winner
+}
+
+// A snapshot of the population that simulate() hands to its observer
+// after scoring each generation.
+pub struct Report<'a> {
+    pub generation: usize,
+    pub environment: &'a str,
+    pub population: &'a [Flib],
+    pub scores: &'a [f32],
+}
+
+// How a flib came to be.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(into = "String")]
+pub enum Origin {
+    // One of the randomly generated flibs that a run starts with.
+    Initial,
+    // The child of two parents.
+    Crossover(Crossover),
+    // The child of two parents, with a single gene then changed.
+    CrossoverMutation(Crossover),
+    // A copy of one parent with a single gene changed.
+    Mutation,
+}
+
+impl fmt::Display for Origin {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origin::Initial => write!(f, "initial"),
+            Origin::Crossover(crossover) => write!(f, "crossover:{}", crossover),
+            Origin::CrossoverMutation(crossover) => {
+                write!(f, "crossover:{}+mutation", crossover)
+            }
+            Origin::Mutation => write!(f, "mutation"),
+        }
+    }
+}
+
+impl From<Origin> for String {
+    fn from(origin: Origin) -> String {
+        origin.to_string()
+    }
+}
+
+// A newly created flib, which simulate() reports once it has been scored.
+// Every flib in a run gets its own ID, counting up from 0.
+pub struct Birth<'a> {
+    pub id: usize,
+    // The generation in which the flib was first scored.
+    pub generation: usize,
+    pub parents: &'a [usize],
+    pub origin: Origin,
+    pub chromosome: &'a str,
+    pub fitness: f32,
+}
+
+// What simulate() should do after its observer has seen a generation.
+#[derive(Debug, PartialEq)]
+pub enum Control {
+    Continue,
+    Stop,
+}
+
+// Receives progress reports from simulate() while it runs.
+pub trait Observer {
+    fn generation(&mut self, report: &Report) -> Control;
+
+    // Describe a single breeding step, such as a crossover or a mutation.
+    fn event(&mut self, _text: &str) {}
+
+    // Record a new flib.  Births are reported in order of ID, before the
+    // generation they were scored in.
+    fn birth(&mut self, _birth: &Birth) {}
+}
+
+// Observer that prints every generation and breeding step to stdout.
+pub struct PrintObserver;
+
+impl Observer for PrintObserver {
+    fn generation(&mut self, report: &Report) -> Control {
+        let heading = if report.generation == 0 {
+            "Initial population:".to_string()
+        } else {
+            format!("Generation {}:", report.generation)
+        };
+        output_population(heading, report.population);
+        println!("{:?}", report.scores);
+        Control::Continue
+    }
+
+    fn event(&mut self, text: &str) {
+        println!("{}", text);
+    }
+}
+
+// Observer that ignores everything, for batch runs.
+pub struct SilentObserver;
+
+impl Observer for SilentObserver {
+    fn generation(&mut self, _report: &Report) -> Control {
+        Control::Continue
+    }
+}
+
+// Everything that controls a single run of simulate().
+#[derive(Clone, Debug)]
+pub struct SimParams {
+    // Sequence of symbols representing the environment
+    pub environment: String,
+    pub population_size: usize,
+    pub num_states: usize,
+    pub operators: Operators,
+    pub replacement: Replacement,
+    pub search: Search,
+    // Only used when search is Search::Anneal.
+    pub annealing: Annealing,
+    pub horizon: Horizon,
+    // Stop as soon as a flib scores at least this much.
+    pub target_score: f32,
+    // Give up after this many generations; None runs until some flib
+    // reaches the target score.
+    pub max_generations: Option<usize>,
+    // Number of scores to keep in the fitness cache; 0 disables it.  Only
+    // window horizons use the cache: with ahead horizons simulate() just
+    // rescores the flibs that changed, which is faster than a lookup.
+    pub cache_size: usize,
+}
+
+impl Default for SimParams {
+    fn default() -> SimParams {
+        let environment = String::from("011001");
+        SimParams {
+            num_states: environment.len() / 2 + 1,
+            environment,
+            population_size: 10,
+            operators: Operators::default(),
+            replacement: Replacement::default(),
+            search: Search::default(),
+            annealing: Annealing::default(),
+            horizon: Horizon::default(),
+            target_score: 1.0,
+            max_generations: None,
+            cache_size: DEFAULT_CACHE_SIZE,
+        }
+    }
+}
+
+pub const DEFAULT_CACHE_SIZE: usize = 4096;
+
+// How a run of simulate() ended.
+#[derive(Debug)]
+pub struct Outcome {
+    // Number of the last generation that was scored.
+    pub generation: usize,
+    // The chromosome of the first flib to reach the target score, if any
+    // did.  With the default target this is a perfect predictor.
+    pub solution: Option<String>,
+    // The chromosome of the best-scoring flib in the last generation, and
+    // its score.  This is the solution, if there is one.
+    pub best: String,
+    pub best_score: f32,
+    // The ID of the best flib, for runs that report births.
+    pub best_id: Option<usize>,
+    // How often scores came from the fitness cache.  Only flibs that
+    // the compiled scorer can't handle go through the cache.
+    pub cache: CacheStats,
+}
+
+// Score flibs that the compiled scorer can't handle, through the cache.
+fn score_cached(
+    cache: &mut FitnessCache,
+    flibs: &mut [Flib],
+    environment: &str,
+    horizon: Horizon,
+    scores: &mut Vec<f32>,
+) {
+    scores.clear();
+    for flib in flibs.iter_mut() {
+        scores.push(cache.score(flib, environment, horizon, |flib| {
+            flib.predict_horizon(environment, horizon)
+        }));
+    }
+}
+
+// A new flib bred by breed(), with the IDs of its parents.
+struct Offspring {
+    chromosome: String,
+    parents: Vec<usize>,
+    origin: Origin,
+}
+
+// Breed `count` offspring from a scored population for the generational
+// and evolution-strategy schemes.  Each is the child of two selected
+// parents, mutated with the mutation rate.  Worst-best selection crosses
+// every flib in turn with the best-scoring one.
+fn breed<R: Rng>(
+    population: &[Flib],
+    scores: &[f32],
+    ids: &[usize],
+    operators: &Operators,
+    count: usize,
+    rng: &mut R,
+) -> Vec<Offspring> {
+    let (_, max_index) = find_minmax(scores);
+    (0..count)
+        .map(|i| {
+            let (parent1, parent2) = match operators.selection {
+                Selection::WorstBest => (i % population.len(), max_index),
+                Selection::Tournament(size) => {
+                    (tournament(scores, size, rng), tournament(scores, size, rng))
+                }
+            };
+            let mut chromosome = operators.crossover.combine(
+                &population[parent1].as_chromosome(),
+                &population[parent2].as_chromosome(),
+                rng,
+            );
+            let mut origin = Origin::Crossover(operators.crossover);
+            if rng.gen::<f32>() < operators.mutation_rate {
+                chromosome = mutate(&chromosome, rng);
+                origin = Origin::CrossoverMutation(operators.crossover);
+            }
+            let mut parents = vec![ids[parent1], ids[parent2]];
+            parents.dedup();
+            Offspring {
+                chromosome,
+                parents,
+                origin,
+            }
+        })
+        .collect()
+}
+
+pub fn simulate<R: Rng>(params: &SimParams, rng: &mut R, observer: &mut dyn Observer) -> Outcome {
+    match params.search {
+        Search::Genetic => {}
+        Search::HillClimb => return local_search::hill_climb(params, rng, observer),
+        Search::Anneal => return local_search::anneal(params, rng, observer),
+    }
+
+    let environment = &params.environment;
+    let operators = &params.operators;
+
+    // Create flibs
+    let mut population: Vec<Flib> = vec![];
+    for _i in 0..params.population_size {
+        let mut newflib = Flib {
+            num_states: 0,
+            current_state: 0,
+            states: vec![],
+        };
+        newflib.randomize(params.num_states, rng);
+        population.push(newflib);
+    }
+
+    // The compiled scorer gives the same scores as Flib::predict() much
+    // faster, but only handles environments made of 0s and 1s and doesn't
+    // do windowed horizons.  It keeps its compiled copy of the population
+    // up to date as flibs change, and only the flibs in `stale`, which
+    // changed since they were last scored, are scored again.  That's much
+    // cheaper than looking every flib up in the fitness cache, so other
+    // horizons are the only ones scored through the cache.
+    let mut scorer = compiled::Scorer::with_horizon(environment, params.horizon);
+    if let Some(scorer) = scorer.as_mut() {
+        scorer.compile(&population);
+    }
+    let mut cache = FitnessCache::new(params.cache_size);
+    let mut scores = vec![0.0; population.len()];
+    let mut stale: Vec<usize> = (0..population.len()).collect();
+    let mut fresh = vec![];
+
+    // ids[i] is the ID of population[i].  Flibs created since the last
+    // scoring are reported as births once they have scores.
+    let mut ids: Vec<usize> = (0..population.len()).collect();
+    let mut next_id = population.len();
+    let mut births: Vec<(usize, Vec<usize>, Origin)> = (0..population.len())
+        .map(|index| (index, vec![], Origin::Initial))
+        .collect();
+
+    let mut generation = 0;
+    loop {
+        // Score predictions based on the environment.  The score is a
+        // decimal value between 0.0 and 1.0, where 1.0 is a perfect predictor
+        // and 0.0 would be a perfect anti-predictor.
+        match scorer.as_ref() {
+            Some(scorer) => {
+                scorer.scores_of(&stale, &mut fresh);
+                for (&index, &score) in stale.iter().zip(&fresh) {
+                    scores[index] = score;
+                }
+            }
+            None => score_cached(
+                &mut cache,
+                &mut population,
+                environment,
+                params.horizon,
+                &mut scores,
+            ),
+        }
+        stale.clear();
+        for (index, parents, origin) in births.drain(..) {
+            observer.birth(&Birth {
+                id: ids[index],
+                generation,
+                parents: &parents,
+                origin,
+                chromosome: &population[index].as_chromosome(),
+                fitness: scores[index],
+            });
+        }
+        let control = observer.generation(&Report {
+            generation,
+            environment,
+            population: &population,
+            scores: &scores,
+        });
+
+        // Check if we have a good enough match
+        if let Some(v) = find_score(&scores, params.target_score) {
+            return Outcome {
+                generation,
+                solution: Some(population[v].as_chromosome()),
+                best: population[v].as_chromosome(),
+                best_score: scores[v],
+                best_id: Some(ids[v]),
+                cache: cache.stats(),
+            };
+        }
+        if control == Control::Stop || Some(generation) == params.max_generations {
+            let (_, max_index) = find_minmax(&scores);
+            return Outcome {
+                generation,
+                solution: None,
+                best: population[max_index].as_chromosome(),
+                best_score: scores[max_index],
+                best_id: Some(ids[max_index]),
+                cache: cache.stats(),
+            };
+        }
+
+        match params.replacement {
+            Replacement::SteadyState => {
+                // Cross-breed the worst-scoring flib, or the winner of a
+                // tournament, with the best-scoring one, replacing the
+                // worst-scoring.
+                let (min_index, max_index) = find_minmax(&scores);
+                observer.event(&format!(
+                    "Worst-scoring index: {} {}",
+                    min_index, scores[min_index]
+                ));
+                observer.event(&format!(
+                    " Best-scoring index: {} {}",
+                    max_index, scores[max_index]
+                ));
+                let (parent1, parent2) = match operators.selection {
+                    Selection::WorstBest => (min_index, max_index),
+                    Selection::Tournament(size) => (
+                        tournament(&scores, size, rng),
+                        tournament(&scores, size, rng),
+                    ),
+                };
+                let embryo = operators.crossover.combine(
+                    &population[parent1].as_chromosome(),
+                    &population[parent2].as_chromosome(),
+                    rng,
+                );
+                observer.event(&format!("New chromosome from crossing: {}", embryo));
+                population[min_index].from_chromosome(embryo);
+                let mut parents = vec![ids[parent1], ids[parent2]];
+                parents.dedup();
+                ids[min_index] = next_id;
+                next_id += 1;
+                births.push((min_index, parents, Origin::Crossover(operators.crossover)));
+                stale.push(min_index);
+                if let Some(scorer) = scorer.as_mut() {
+                    scorer.update(min_index, &population[min_index]);
+                }
+
+                let random_index = rng.gen_range(0, population.len());
+                if random_index != max_index
+                    && random_index != min_index
+                    && rng.gen::<f32>() < operators.mutation_rate
+                {
+                    let subject = population[random_index].as_chromosome();
+                    let mutant = mutate(&subject, rng);
+                    observer.event(&format!(
+                        "Mutating #{} {} to {}",
+                        random_index, subject, mutant
+                    ));
+                    population[random_index].from_chromosome(mutant);
+                    births.push((random_index, vec![ids[random_index]], Origin::Mutation));
+                    ids[random_index] = next_id;
+                    next_id += 1;
+                    stale.push(random_index);
+                    if let Some(scorer) = scorer.as_mut() {
+                        scorer.update(random_index, &population[random_index]);
+                    }
+                }
+            }
+            Replacement::Generational => {
+                let offspring = breed(&population, &scores, &ids, operators, population.len(), rng);
+                observer.event(&format!(
+                    "Replacing the population with {} offspring",
+                    offspring.len()
+                ));
+                for (index, child) in offspring.into_iter().enumerate() {
+                    population[index].from_chromosome(child.chromosome);
+                    ids[index] = next_id;
+                    next_id += 1;
+                    births.push((index, child.parents, child.origin));
+                }
+                stale.extend(0..population.len());
+                if let Some(scorer) = scorer.as_mut() {
+                    scorer.compile(&population);
+                }
+            }
+            Replacement::Comma(lambda) | Replacement::Plus(lambda) => {
+                // Offspring have to be scored now, to choose between them.
+                let offspring = breed(&population, &scores, &ids, operators, lambda, rng);
+                let mut children: Vec<Flib> = offspring
+                    .iter()
+                    .map(|child| make_from_chromosome(child.chromosome.clone()))
+                    .collect();
+                let mut child_scores = vec![];
+                match scorer.as_mut() {
+                    Some(scorer) => scorer.score_population(&children, &mut child_scores),
+                    None => score_cached(
+                        &mut cache,
+                        &mut children,
+                        environment,
+                        params.horizon,
+                        &mut child_scores,
+                    ),
+                }
+                for (i, child) in offspring.iter().enumerate() {
+                    observer.birth(&Birth {
+                        id: next_id,
+                        generation: generation + 1,
+                        parents: &child.parents,
+                        origin: child.origin,
+                        chromosome: &child.chromosome,
+                        fitness: child_scores[i],
+                    });
+                    next_id += 1;
+                }
+                let first_child = next_id - lambda;
+                let mut pool: Vec<_> = children
+                    .into_iter()
+                    .zip(first_child..next_id)
+                    .zip(child_scores)
+                    .collect();
+                if params.replacement == Replacement::Plus(lambda) {
+                    pool.extend(
+                        population
+                            .drain(..)
+                            .zip(ids.drain(..))
+                            .zip(scores.drain(..)),
+                    );
+                }
+                // The sort is stable, so offspring win ties with parents.
+                pool.sort_by(|(_, a), (_, b)| b.partial_cmp(a).unwrap());
+                observer.event(&format!(
+                    "Keeping the best {} of {} flibs",
+                    params.population_size,
+                    pool.len()
+                ));
+                pool.truncate(params.population_size);
+                // Every survivor already has a score, so none are stale.
+                population.clear();
+                ids.clear();
+                scores.clear();
+                for ((flib, id), score) in pool {
+                    population.push(flib);
+                    ids.push(id);
+                    scores.push(score);
+                }
+                if let Some(scorer) = scorer.as_mut() {
+                    scorer.compile(&population);
+                }
+            }
+        }
+
+        generation += 1;
+    }
+}
+
+// Find the first score that reaches `target`.
+pub fn find_score(vec: &[f32], target: f32) -> Option<usize> {
+    vec.iter().position(|&score| score >= target)
+}
+
+// Find highest and lowest scores
+pub fn find_minmax(vec: &[f32]) -> (usize, usize) {
+    let mut min_index: usize = 0;
+    let mut max_index: usize = 0;
+    let mut min_score = 1.0;
+    let mut max_score = 0.0;
+
+    for (i, &score) in vec.iter().enumerate() {
+        if score > max_score {
+            max_score = score;
+            max_index = i;
+        }
+        if score < min_score {
+            min_score = score;
+            min_index = i;
+        }
+    }
+    (min_index, max_index)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_echo_flib() {
+        // Test a flib that just echoes its environment
+        let mut flib = Flib {
+            num_states: 1,
+            current_state: 0,
+            states: vec![vec![('0', 0), ('1', 0)]],
+        };
+
+        assert_eq!(flib.as_chromosome(), "0A1A");
+
+        // Input a 0 and 1, and check that we get a 0 or 1 back
+        assert_eq!(flib.transition('0'), '0');
+        assert_eq!(flib.transition('1'), '1');
+    }
+
+    #[test]
+    fn test_flib_round_trip() {
+        // Test that a flib with two states round-trips to string and back.
+        let mut flib = Flib {
+            num_states: 1,
+            current_state: 0,
+            states: vec![vec![('0', 1), ('1', 1)], vec![('1', 0), ('0', 0)]],
+        };
+        assert_eq!(flib.as_chromosome(), "0B1B1A0A");
+
+        // After round-trip, the chromosome value should be the same
+        flib.from_chromosome(flib.as_chromosome());
+        assert_eq!(flib.as_chromosome(), "0B1B1A0A");
+    }
+
+    #[test]
+    fn test_flib_chromosome_wrong_length() {
+        // Test that supplying a chromosome whose length isn't a multiple of four will panic.
+        make_from_chromosome(String::from("0A1"));
+    }
+
+    #[test]
+    fn test_flib_mutation() {
+        // Test that mutation at least runs without panicking.
+        let mut rng = rand::thread_rng();
+        mutate_at_index(&String::from("0A1B1A0B"), 0, &mut rng);
+        mutate_at_index(&String::from("0A1B1A0B"), 1, &mut rng);
+    }
+
+    #[test]
+    fn test_two_state_flib() {
+        // Test a flib with two states
+        let mut flib = Flib {
+            num_states: 1,
+            current_state: 0,
+            states: vec![vec![('0', 1), ('1', 1)], vec![('1', 0), ('0', 0)]],
+        };
+        assert_eq!(flib.as_chromosome(), "0B1B1A0A");
+        flib.transition('0');
+        assert_eq!(flib.current_state, 1);
+        flib.transition('0');
+        assert_eq!(flib.current_state, 0);
+    }
+
+    #[test]
+    fn test_randomize_method() {
+        let mut flib = Flib {
+            num_states: 1,
+            current_state: 0,
+            states: vec![],
+        };
+
+        flib.randomize(5, &mut rand::thread_rng());
+        assert_eq!(flib.num_states, 5);
+    }
+
+    #[test]
+    fn test_horizon_scores() {
+        // An echo is never right two steps ahead on 0011, and always
+        // right a whole period ahead.
+        let mut echo = make_from_chromosome(String::from("0A1A"));
+        assert_eq!(echo.predict_horizon("0011", Horizon::Ahead(2)), 0.0);
+        assert_eq!(echo.predict_horizon("0011", Horizon::Ahead(4)), 1.0);
+        assert_eq!(
+            echo.predict_horizon("0011", Horizon::Ahead(1)),
+            echo.predict("0011")
+        );
+        // Rolled out two steps, it gets 2 of the 8 forecast symbols right
+        // in each copy of 0011.
+        assert_eq!(echo.predict_horizon("0011", Horizon::Window(2)), 0.25);
+
+        // A flib that alternates 1, 0, 1, ... whatever its input predicts
+        // 01 perfectly, both next-symbol and rolled out, but is always
+        // wrong two steps ahead.
+        let mut alternator = make_from_chromosome(String::from("1B1B0A0A"));
+        assert_eq!(alternator.predict("01"), 1.0);
+        assert_eq!(alternator.predict_horizon("01", Horizon::Window(3)), 1.0);
+        assert_eq!(alternator.predict_horizon("01", Horizon::Ahead(2)), 0.0);
+    }
+
+    #[test]
+    fn test_parse_chromosome() {
+        assert_eq!(
+            parse_chromosome("0B1B1A0A").unwrap().as_chromosome(),
+            "0B1B1A0A"
+        );
+        assert!(parse_chromosome("").is_err());
+        assert!(parse_chromosome("0A1").is_err());
+        assert!(parse_chromosome("0A1C").unwrap_err().contains("position 3"));
+        assert!(parse_chromosome("xA1A").is_err());
+    }
+
+    #[test]
+    fn test_horizon_names() {
+        assert_eq!("ahead:3".parse(), Ok(Horizon::Ahead(3)));
+        assert_eq!("window:2".parse(), Ok(Horizon::Window(2)));
+        assert_eq!(Horizon::Window(2).to_string(), "window:2");
+        assert!("ahead:0".parse::<Horizon>().is_err());
+        assert!("behind:1".parse::<Horizon>().is_err());
+    }
+
+    #[test]
+    fn test_diversity() {
+        assert_eq!(diversity(&["0101".to_string(), "0101".to_string()]), 0.0);
+        assert_eq!(diversity(&["0000".to_string(), "1111".to_string()]), 1.0);
+        assert_eq!(diversity(&["0A1A".to_string()]), 0.0);
+        // Pairs differ in 1, 2 and 1 of their 2 symbols.
+        let strings = ["00", "01", "11"].map(String::from);
+        assert_eq!(diversity(&strings), 4.0 / 6.0);
+    }
+
+    #[test]
+    fn test_reachable_states() {
+        // State C is only reachable from D, which is never reached.
+        let flib = make_from_chromosome(String::from("0A1B1A0A0B1B0C1C"));
+        assert_eq!(flib.reachable_states(), 2);
+        let flib = make_from_chromosome(String::from("0B1B1A0A"));
+        assert_eq!(flib.reachable_states(), 2);
+        let flib = make_from_chromosome(String::from("0A1A0A1A"));
+        assert_eq!(flib.reachable_states(), 1);
+    }
+
+    #[test]
+    fn test_canonical_chromosome() {
+        let flib = make_from_chromosome(String::from("0A1B1A0A0B1B0C1C"));
+        assert_eq!(flib.canonical_chromosome(), "0A1B1A0A");
+        // Swapping the names of B and C changes nothing.
+        let a = make_from_chromosome(String::from("0C1B1A0A0A1C"));
+        let b = make_from_chromosome(String::from("0B1C0A1B1A0A"));
+        assert_eq!(a.canonical_chromosome(), "0B1C0A1B1A0A");
+        assert_eq!(a.canonical_chromosome(), b.canonical_chromosome());
+    }
+
+    #[test]
+    fn test_cache_keeps_outcome() {
+        use rand::{SeedableRng, StdRng};
+        // Caching only saves work, so a seeded run ends the same way with
+        // and without it.
+        for &horizon in &[Horizon::Ahead(1), Horizon::Window(2)] {
+            let mut outcomes = vec![];
+            for &cache_size in &[0, 4, DEFAULT_CACHE_SIZE] {
+                let params = SimParams {
+                    environment: String::from("0010111"),
+                    population_size: 20,
+                    horizon,
+                    max_generations: Some(300),
+                    cache_size,
+                    ..SimParams::default()
+                };
+                let mut rng = StdRng::from_seed(&[39][..]);
+                let outcome = simulate(&params, &mut rng, &mut SilentObserver);
+                outcomes.push((outcome.generation, outcome.best, outcome.best_score));
+                if cache_size == DEFAULT_CACHE_SIZE && horizon == Horizon::Window(2) {
+                    assert!(outcome.cache.hit_rate() > 0.5);
+                }
+                if let Horizon::Ahead(_) = horizon {
+                    assert_eq!(outcome.cache.lookups(), 0);
+                }
+            }
+            assert_eq!(outcomes[0], outcomes[1]);
+            assert_eq!(outcomes[0], outcomes[2]);
+        }
+    }
+
+    #[test]
+    fn test_replacement_names() {
+        assert_eq!("steady-state".parse(), Ok(Replacement::SteadyState));
+        assert_eq!("plus:20".parse(), Ok(Replacement::Plus(20)));
+        assert_eq!(Replacement::Comma(30).to_string(), "comma:30");
+        assert!("comma:0".parse::<Replacement>().is_err());
+        assert!("generational:5".parse::<Replacement>().is_err());
+    }
+
+    // Records the best score and population size of each generation.
+    struct Progress(Vec<(f32, usize)>);
+
+    impl Observer for Progress {
+        fn generation(&mut self, report: &Report) -> Control {
+            let best = report.scores.iter().cloned().fold(0.0, f32::max);
+            self.0.push((best, report.population.len()));
+            Control::Continue
+        }
+    }
+
+    // Checks every reported score against Flib::predict().
+    struct CheckScores;
+
+    impl Observer for CheckScores {
+        fn generation(&mut self, report: &Report) -> Control {
+            for (flib, &score) in report.population.iter().zip(report.scores) {
+                let mut flib = make_from_chromosome(flib.as_chromosome());
+                assert_eq!(flib.predict(report.environment), score);
+            }
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn test_only_stale_flibs_rescored() {
+        use rand::{SeedableRng, StdRng};
+        // Flibs that didn't change keep their old scores, which have to
+        // still be right.
+        for &replacement in &[
+            Replacement::SteadyState,
+            Replacement::Generational,
+            Replacement::Comma(20),
+            Replacement::Plus(10),
+        ] {
+            let params = SimParams {
+                environment: String::from("0010111011"),
+                population_size: 10,
+                replacement,
+                max_generations: Some(200),
+                ..SimParams::default()
+            };
+            let mut rng = StdRng::from_seed(&[44][..]);
+            simulate(&params, &mut rng, &mut CheckScores);
+        }
+    }
+
+    #[test]
+    fn test_replacement_schemes() {
+        use rand::{SeedableRng, StdRng};
+        for &replacement in &[
+            Replacement::Generational,
+            Replacement::Comma(20),
+            Replacement::Plus(10),
+        ] {
+            for &selection in &[Selection::WorstBest, Selection::Tournament(2)] {
+                for &horizon in &[Horizon::Ahead(1), Horizon::Window(2)] {
+                    let params = SimParams {
+                        population_size: 10,
+                        operators: Operators {
+                            selection,
+                            ..Operators::default()
+                        },
+                        replacement,
+                        horizon,
+                        max_generations: Some(100),
+                        ..SimParams::default()
+                    };
+                    let mut rng = StdRng::from_seed(&[43][..]);
+                    let mut progress = Progress(vec![]);
+                    let outcome = simulate(&params, &mut rng, &mut progress);
+                    assert!(outcome.best_score > 0.5);
+                    assert!(progress.0.iter().all(|&(_, size)| size == 10));
+                    if let Replacement::Plus(_) = replacement {
+                        // Parents compete with their offspring, so the best
+                        // score never falls.
+                        assert!(progress.0.windows(2).all(|w| w[0].0 <= w[1].0));
+                    }
+                }
+            }
+        }
+    }
+}
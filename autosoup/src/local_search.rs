@@ -0,0 +1,465 @@
+// Single-individual searches over flibs, as baselines for the GA.
+//
+// Both searches move between neighbouring flibs, where the neighbours of
+// a flib are every other chromosome that mutate_at_index() can produce
+// from it: one output flipped, or one transition sent to a different
+// state.
+//
+// Steepest-ascent hill climbing scores every neighbour and moves to the
+// best one while that's an improvement.  At a local optimum it restarts
+// from a new random flib.  Simulated annealing tries one random mutation
+// per step, always accepting improvements and accepting a drop of d in
+// score with probability exp(-d / T), where the temperature T falls
+// according to a cooling schedule.
+//
+// simulate() runs these when asked for them, and they report to the
+// observer as the GA does, with a population of the one current flib.  A
+// generation is one step: a whole neighbourhood for hill climbing, and a
+// single mutation for annealing.
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::FitnessCache;
+use crate::compiled::Scorer;
+use crate::{
+    make_from_chromosome, mutate, state_to_char, Birth, Control, Flib, Observer, Origin, Outcome,
+    Report, SimParams,
+};
+
+// How the annealing temperature falls as the steps go by.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum Cooling {
+    // Multiply the temperature by this factor every step.
+    Exponential(f32),
+    // Fall in a straight line to zero at max_generations.
+    Linear,
+    // Divide the initial temperature by ln(step + e), the slow schedule
+    // of the classic convergence proofs.
+    Logarithmic,
+}
+
+impl Cooling {
+    // The temperature at `step`, starting from `initial`.
+    pub fn temperature(self, initial: f32, step: usize, max_steps: Option<usize>) -> f32 {
+        match self {
+            Cooling::Exponential(rate) => initial * rate.powf(step as f32),
+            Cooling::Linear => {
+                let max_steps = max_steps.expect("linear cooling needs max_generations");
+                initial * (1.0 - (step as f32) / (max_steps.max(1) as f32)).max(0.0)
+            }
+            Cooling::Logarithmic => initial / ((step as f32) + std::f32::consts::E).ln(),
+        }
+    }
+}
+
+impl FromStr for Cooling {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Cooling, String> {
+        let parsed = match s.split_once(':') {
+            Some(("exponential", rate)) => rate
+                .parse()
+                .ok()
+                .filter(|&r: &f32| r > 0.0 && r < 1.0)
+                .map(Cooling::Exponential),
+            None if s == "linear" => Some(Cooling::Linear),
+            None if s == "logarithmic" => Some(Cooling::Logarithmic),
+            _ => None,
+        };
+        parsed.ok_or_else(|| {
+            format!(
+                "unknown cooling schedule '{}'; expected exponential:RATE, linear or logarithmic",
+                s
+            )
+        })
+    }
+}
+
+impl TryFrom<String> for Cooling {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Cooling, String> {
+        s.parse()
+    }
+}
+
+impl From<Cooling> for String {
+    fn from(cooling: Cooling) -> String {
+        cooling.to_string()
+    }
+}
+
+impl fmt::Display for Cooling {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cooling::Exponential(rate) => write!(f, "exponential:{}", rate),
+            Cooling::Linear => write!(f, "linear"),
+            Cooling::Logarithmic => write!(f, "logarithmic"),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Annealing {
+    // Scores are fractions, so a useful starting temperature is around
+    // the score of a single symbol, 1 / (2 * environment length).
+    pub temperature: f32,
+    pub cooling: Cooling,
+}
+
+impl Default for Annealing {
+    fn default() -> Annealing {
+        Annealing {
+            temperature: 0.05,
+            cooling: Cooling::Exponential(0.999),
+        }
+    }
+}
+
+// Every chromosome other than `chromosome` that mutate_at_index() can make
+// from it.
+pub fn neighbours(chromosome: &str) -> Vec<String> {
+    let num_states = chromosome.len() / 4;
+    let mut result = vec![];
+    for (i, ch) in chromosome.char_indices() {
+        let replacements: Vec<char> = if i % 2 == 0 {
+            vec![if ch == '0' { '1' } else { '0' }]
+        } else {
+            (0..num_states)
+                .map(state_to_char)
+                .filter(|&state| state != ch)
+                .collect()
+        };
+        for replacement in replacements {
+            let mut neighbour = chromosome.to_string();
+            neighbour.replace_range(i..=i, &replacement.to_string());
+            result.push(neighbour);
+        }
+    }
+    result
+}
+
+// Scores flibs as simulate() does: compiled where possible, and otherwise
+// through the fitness cache.
+struct Evaluator<'a> {
+    params: &'a SimParams,
+    scorer: Option<Scorer>,
+    cache: FitnessCache,
+}
+
+impl<'a> Evaluator<'a> {
+    fn new(params: &'a SimParams) -> Evaluator<'a> {
+        Evaluator {
+            params,
+            scorer: Scorer::with_horizon(&params.environment, params.horizon),
+            cache: FitnessCache::new(params.cache_size),
+        }
+    }
+
+    fn score(&mut self, flib: &mut Flib) -> f32 {
+        let (environment, horizon) = (&self.params.environment, self.params.horizon);
+        match self.scorer.as_mut() {
+            Some(scorer) => scorer.score(flib),
+            None => self.cache.score(flib, environment, horizon, |flib| {
+                flib.predict_horizon(environment, horizon)
+            }),
+        }
+    }
+}
+
+// The flib a search is currently at, and the best one it has seen.
+struct Walk {
+    current: Flib,
+    score: f32,
+    id: usize,
+    next_id: usize,
+    best: (String, f32, usize),
+    solution: Option<(String, f32, usize)>,
+}
+
+impl Walk {
+    fn start<R: Rng>(evaluator: &mut Evaluator, rng: &mut R, observer: &mut dyn Observer) -> Walk {
+        let mut walk = Walk {
+            current: make_from_chromosome(String::new()),
+            score: 0.0,
+            id: 0,
+            next_id: 0,
+            best: (String::new(), -1.0, 0),
+            solution: None,
+        };
+        walk.restart(evaluator, 0, rng, observer);
+        walk
+    }
+
+    // Move to `flib`, with score `score`.
+    fn step(
+        &mut self,
+        flib: Flib,
+        score: f32,
+        origin: Origin,
+        generation: usize,
+        target: f32,
+        observer: &mut dyn Observer,
+    ) {
+        let parents = match origin {
+            Origin::Initial => vec![],
+            _ => vec![self.id],
+        };
+        self.current = flib;
+        self.score = score;
+        self.id = self.next_id;
+        self.next_id += 1;
+        let chromosome = self.current.as_chromosome();
+        observer.birth(&Birth {
+            id: self.id,
+            generation,
+            parents: &parents,
+            origin,
+            chromosome: &chromosome,
+            fitness: score,
+        });
+        if score > self.best.1 {
+            self.best = (chromosome.clone(), score, self.id);
+        }
+        if self.solution.is_none() && score >= target {
+            self.solution = Some((chromosome, score, self.id));
+        }
+    }
+
+    fn restart<R: Rng>(
+        &mut self,
+        evaluator: &mut Evaluator,
+        generation: usize,
+        rng: &mut R,
+        observer: &mut dyn Observer,
+    ) {
+        let mut flib = make_from_chromosome(String::new());
+        flib.randomize(evaluator.params.num_states, rng);
+        let score = evaluator.score(&mut flib);
+        let target = evaluator.params.target_score;
+        self.step(flib, score, Origin::Initial, generation, target, observer);
+    }
+
+    // Report the current flib, and return the outcome if the search is
+    // over.
+    fn report(
+        &self,
+        evaluator: &Evaluator,
+        generation: usize,
+        observer: &mut dyn Observer,
+    ) -> Option<Outcome> {
+        let params = evaluator.params;
+        let control = observer.generation(&Report {
+            generation,
+            environment: &params.environment,
+            population: std::slice::from_ref(&self.current),
+            scores: &[self.score],
+        });
+        let (best, best_score, best_id) = match (&self.solution, &self.best) {
+            (Some(solution), _) => solution.clone(),
+            (None, best)
+                if control == Control::Stop || Some(generation) == params.max_generations =>
+            {
+                best.clone()
+            }
+            _ => return None,
+        };
+        Some(Outcome {
+            generation,
+            solution: self.solution.as_ref().map(|s| s.0.clone()),
+            best,
+            best_score,
+            best_id: Some(best_id),
+            cache: evaluator.cache.stats(),
+        })
+    }
+}
+
+pub fn hill_climb<R: Rng>(params: &SimParams, rng: &mut R, observer: &mut dyn Observer) -> Outcome {
+    assert!(params.num_states >= 1, "flibs need at least one state");
+    let mut evaluator = Evaluator::new(params);
+    let mut walk = Walk::start(&mut evaluator, rng, observer);
+    let mut generation = 0;
+    loop {
+        if let Some(outcome) = walk.report(&evaluator, generation, observer) {
+            return outcome;
+        }
+        generation += 1;
+
+        let mut best: Option<(Flib, f32)> = None;
+        for chromosome in neighbours(&walk.current.as_chromosome()) {
+            let mut neighbour = make_from_chromosome(chromosome);
+            let score = evaluator.score(&mut neighbour);
+            if best.as_ref().is_none_or(|(_, s)| score > *s) {
+                best = Some((neighbour, score));
+            }
+        }
+        match best {
+            Some((neighbour, score)) if score > walk.score => {
+                observer.event(&format!(
+                    "Climbing to {} {}",
+                    neighbour.as_chromosome(),
+                    score
+                ));
+                walk.step(
+                    neighbour,
+                    score,
+                    Origin::Mutation,
+                    generation,
+                    params.target_score,
+                    observer,
+                );
+            }
+            _ => {
+                observer.event(&format!(
+                    "Local optimum {} {}; restarting",
+                    walk.current.as_chromosome(),
+                    walk.score
+                ));
+                walk.restart(&mut evaluator, generation, rng, observer);
+            }
+        }
+    }
+}
+
+pub fn anneal<R: Rng>(params: &SimParams, rng: &mut R, observer: &mut dyn Observer) -> Outcome {
+    assert!(params.num_states >= 1, "flibs need at least one state");
+    let annealing = params.annealing;
+    let mut evaluator = Evaluator::new(params);
+    let mut walk = Walk::start(&mut evaluator, rng, observer);
+    let mut generation = 0;
+    loop {
+        if let Some(outcome) = walk.report(&evaluator, generation, observer) {
+            return outcome;
+        }
+        let temperature = annealing.cooling.temperature(
+            annealing.temperature,
+            generation,
+            params.max_generations,
+        );
+        generation += 1;
+
+        let mut candidate = make_from_chromosome(mutate(&walk.current.as_chromosome(), rng));
+        let score = evaluator.score(&mut candidate);
+        let loss = walk.score - score;
+        if loss <= 0.0 || rng.gen::<f32>() < (-loss / temperature).exp() {
+            observer.event(&format!(
+                "Accepting {} {} at temperature {}",
+                candidate.as_chromosome(),
+                score,
+                temperature
+            ));
+            walk.step(
+                candidate,
+                score,
+                Origin::Mutation,
+                generation,
+                params.target_score,
+                observer,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::genealogy::Genealogy;
+    use crate::{mutate_at_index, simulate, Search};
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn test_neighbours() {
+        let all = neighbours("0B1A1A0A");
+        // Four outputs to flip, and four transitions with one other state
+        // to go to.
+        assert_eq!(all.len(), 8);
+        assert!(all.contains(&String::from("1B1A1A0A")));
+        assert!(all.contains(&String::from("0A1A1A0A")));
+        assert_eq!(neighbours("0A1A").len(), 2);
+
+        let mut rng = StdRng::from_seed(&[45][..]);
+        let chromosome = "0B1C1A0A0C1B";
+        let all = neighbours(chromosome);
+        assert_eq!(all.len(), 6 + 6 * 2);
+        for i in 0..chromosome.len() {
+            let mutant = mutate_at_index(chromosome, i, &mut rng);
+            assert!(mutant == chromosome || all.contains(&mutant));
+        }
+    }
+
+    #[test]
+    fn test_cooling() {
+        let exponential: Cooling = "exponential:0.5".parse().unwrap();
+        assert_eq!(exponential.temperature(1.0, 2, None), 0.25);
+        assert_eq!(Cooling::Linear.temperature(1.0, 25, Some(100)), 0.75);
+        assert_eq!(Cooling::Linear.temperature(1.0, 200, Some(100)), 0.0);
+        assert!((Cooling::Logarithmic.temperature(1.0, 0, None) - 1.0).abs() < 1e-6);
+        assert_eq!(Cooling::Logarithmic.to_string(), "logarithmic");
+        assert!("exponential:1.5".parse::<Cooling>().is_err());
+        assert!("geometric".parse::<Cooling>().is_err());
+    }
+
+    // Records how big each reported population was.
+    struct Sizes(Vec<usize>);
+
+    impl Observer for Sizes {
+        fn generation(&mut self, report: &Report) -> Control {
+            self.0.push(report.population.len());
+            Control::Continue
+        }
+    }
+
+    #[test]
+    fn test_searches_solve() {
+        for &search in &[Search::HillClimb, Search::Anneal] {
+            let params = SimParams {
+                search,
+                max_generations: Some(20000),
+                ..SimParams::default()
+            };
+            let mut rng = StdRng::from_seed(&[45][..]);
+            let mut sizes = Sizes(vec![]);
+            let outcome = simulate(&params, &mut rng, &mut sizes);
+            let solution = outcome.solution.expect("no perfect predictor found");
+            assert_eq!(make_from_chromosome(solution).predict("011001"), 1.0);
+            assert_eq!(sizes.0.len(), outcome.generation + 1);
+            assert!(sizes.0.iter().all(|&size| size == 1));
+
+            // The winner's lineage can be traced as it can for the GA.
+            let mut rng = StdRng::from_seed(&[45][..]);
+            let mut genealogy = Genealogy::new();
+            let outcome = simulate(&params, &mut rng, &mut genealogy);
+            let ancestry = genealogy.ancestry(outcome.best_id.unwrap());
+            assert_eq!(ancestry[0].origin, Origin::Initial);
+            assert_eq!(ancestry.last().unwrap().chromosome, outcome.best);
+        }
+    }
+
+    #[test]
+    fn test_searches_stop() {
+        // One state can't predict this, so both searches run out of
+        // generations and report the best flib they saw.
+        for &search in &[Search::HillClimb, Search::Anneal] {
+            let params = SimParams {
+                environment: String::from("0110100110010110"),
+                num_states: 1,
+                search,
+                max_generations: Some(50),
+                ..SimParams::default()
+            };
+            let mut rng = StdRng::from_seed(&[45][..]);
+            let outcome = simulate(&params, &mut rng, &mut crate::SilentObserver);
+            assert_eq!(outcome.generation, 50);
+            assert!(outcome.solution.is_none());
+            let mut best = make_from_chromosome(outcome.best);
+            assert_eq!(best.predict(&params.environment), outcome.best_score);
+        }
+    }
+}
@@ -0,0 +1,183 @@
+// Output sinks for configured simulation runs.
+//
+// Each sink is an Observer, and a Tee passes every report on to all of
+// the sinks that the configuration asks for.
+
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::config::Config;
+use crate::genealogy::Genealogy;
+use crate::significance;
+use crate::{
+    diversity, find_minmax, make_rng, simulate, Birth, Control, Observer, PrintObserver, Report,
+};
+
+// Pass reports on to several observers.  The run stops if any of them
+// asks it to.
+pub struct Tee<'a>(pub Vec<&'a mut dyn Observer>);
+
+impl<'a> Observer for Tee<'a> {
+    fn generation(&mut self, report: &Report) -> Control {
+        let mut control = Control::Continue;
+        for observer in self.0.iter_mut() {
+            if observer.generation(report) == Control::Stop {
+                control = Control::Stop;
+            }
+        }
+        control
+    }
+
+    fn event(&mut self, text: &str) {
+        for observer in self.0.iter_mut() {
+            observer.event(text);
+        }
+    }
+
+    fn birth(&mut self, birth: &Birth) {
+        for observer in self.0.iter_mut() {
+            observer.birth(birth);
+        }
+    }
+}
+
+// Write one CSV line of statistics per generation.
+pub struct StatsCsv<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> StatsCsv<W> {
+    pub fn new(mut writer: W) -> io::Result<StatsCsv<W>> {
+        writeln!(writer, "environment,generation,best,mean,diversity")?;
+        Ok(StatsCsv { writer })
+    }
+}
+
+impl<W: Write> Observer for StatsCsv<W> {
+    fn generation(&mut self, report: &Report) -> Control {
+        let (_, best) = find_minmax(report.scores);
+        let mean = report.scores.iter().sum::<f32>() / (report.scores.len() as f32);
+        let chromosomes: Vec<String> = report
+            .population
+            .iter()
+            .map(|f| f.as_chromosome())
+            .collect();
+        writeln!(
+            self.writer,
+            "{},{},{},{},{}",
+            report.environment,
+            report.generation,
+            report.scores[best],
+            mean,
+            diversity(&chromosomes)
+        )
+        .expect("Failed to write statistics");
+        Control::Continue
+    }
+}
+
+#[derive(Serialize)]
+struct RunRecord {
+    environment: String,
+    generations: usize,
+    solution: Option<String>,
+}
+
+// An error message for a failure to write `path`.
+pub fn file_error(path: &Path, e: io::Error) -> String {
+    format!("can't write {}: {}", path.display(), e)
+}
+
+// Run a simulation for each environment in `config`, sending progress to
+// the configured sinks.
+pub fn run_configured(config: &Config) -> Result<(), String> {
+    let mut rng = make_rng(config.seed).map_err(|e| e.to_string())?;
+
+    // A single stats file covers every run, so open it up front and share
+    // it between them.
+    let mut stats = match &config.output.stats_csv {
+        Some(path) => Some(
+            File::create(path)
+                .and_then(|file| StatsCsv::new(BufWriter::new(file)))
+                .map_err(|e| file_error(path, e))?,
+        ),
+        None => None,
+    };
+
+    let track_lineage = config.output.lineage_dot.is_some() || config.output.lineage_json.is_some();
+    let mut lineage_dot = String::new();
+    let mut lineage_json = vec![];
+
+    let mut records = vec![];
+    for params in config.params() {
+        let mut print = PrintObserver;
+        let mut genealogy = Genealogy::new();
+        let mut sinks: Vec<&mut dyn Observer> = vec![];
+        if config.output.log.unwrap_or(true) {
+            sinks.push(&mut print);
+        }
+        if let Some(stats) = stats.as_mut() {
+            sinks.push(stats);
+        }
+        if track_lineage {
+            sinks.push(&mut genealogy);
+        }
+        let outcome = simulate(&params, &mut rng, &mut Tee(sinks));
+        if let (true, Some(id)) = (track_lineage, outcome.best_id) {
+            lineage_dot.push_str(&genealogy.to_dot(id, &params.environment));
+            lineage_json.push(genealogy.to_json(id, &params.environment));
+        }
+
+        let target = params.target_score;
+        match (&outcome.solution, target >= 1.0) {
+            (Some(chromosome), true) => println!("Perfect predictor: {}", chromosome),
+            (Some(chromosome), false) => {
+                println!("Predictor scoring at least {}: {}", target, chromosome)
+            }
+            (None, true) => println!("No perfect predictor found"),
+            (None, false) => println!("No predictor scoring at least {} found", target),
+        }
+        if params.cache_size > 0 && outcome.cache.lookups() > 0 {
+            println!("Fitness cache: {}", outcome.cache);
+        }
+        if let Some(samples) = config.output.significance {
+            let report = significance::assess(
+                &outcome.best,
+                &params.environment,
+                params.horizon,
+                samples,
+                &mut rng,
+            );
+            println!("Significance of best flib {}:\n{}", outcome.best, report);
+        }
+        records.push(RunRecord {
+            environment: params.environment.clone(),
+            generations: outcome.generation,
+            solution: outcome.solution,
+        });
+    }
+
+    if let (Some(stats), Some(path)) = (stats.as_mut(), &config.output.stats_csv) {
+        stats.writer.flush().map_err(|e| file_error(path, e))?;
+    }
+    if let Some(path) = &config.output.result_json {
+        write_json(path, &records).map_err(|e| file_error(path, e))?;
+    }
+    // Each run gets a graph of its own in the DOT file, and an entry in
+    // the JSON file's list.
+    if let Some(path) = &config.output.lineage_dot {
+        std::fs::write(path, lineage_dot).map_err(|e| file_error(path, e))?;
+    }
+    if let Some(path) = &config.output.lineage_json {
+        write_json(path, &lineage_json).map_err(|e| file_error(path, e))?;
+    }
+    Ok(())
+}
+
+fn write_json<T: Serialize>(path: &Path, records: &[T]) -> io::Result<()> {
+    let json = serde_json::to_string_pretty(records).expect("Failed to serialize results");
+    std::fs::write(path, json)
+}
@@ -0,0 +1,321 @@
+// Multi-objective evolution of flibs using NSGA-II.
+//
+// Instead of a single score, each flib is judged on two objectives: its
+// prediction accuracy (to be maximized) and the number of states reachable
+// from its starting state (to be minimized).  The result is a Pareto front
+// of machines where no member is both more accurate and smaller than
+// another.
+
+use std::cmp::Ordering;
+
+use rand::Rng;
+use serde::Serialize;
+
+use crate::{make_from_chromosome, mutate, random_combine};
+
+pub struct ParetoParams {
+    pub environment: String,
+    pub population_size: usize,
+    pub generations: usize,
+    pub num_states: usize,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct Individual {
+    pub chromosome: String,
+    pub accuracy: f32,
+    pub states: usize,
+}
+
+impl Individual {
+    fn evaluate(chromosome: String, environment: &str) -> Individual {
+        let mut flib = make_from_chromosome(chromosome.clone());
+        let accuracy = flib.predict(environment);
+        let states = flib.reachable_states();
+        Individual {
+            chromosome,
+            accuracy,
+            states,
+        }
+    }
+
+    // True if `self` is at least as good as `other` on both objectives and
+    // strictly better on one.
+    fn dominates(&self, other: &Individual) -> bool {
+        self.accuracy >= other.accuracy
+            && self.states <= other.states
+            && (self.accuracy > other.accuracy || self.states < other.states)
+    }
+}
+
+// Sort the population into successive non-dominated fronts, returning the
+// indices in each front.  Front 0 is the Pareto front.
+fn non_dominated_sort(population: &[Individual]) -> Vec<Vec<usize>> {
+    let n = population.len();
+    let mut dominated_by: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut domination_count = vec![0; n];
+    let mut fronts = vec![vec![]];
+
+    for p in 0..n {
+        for q in 0..n {
+            if population[p].dominates(&population[q]) {
+                dominated_by[p].push(q);
+            } else if population[q].dominates(&population[p]) {
+                domination_count[p] += 1;
+            }
+        }
+        if domination_count[p] == 0 {
+            fronts[0].push(p);
+        }
+    }
+
+    let mut i = 0;
+    while !fronts[i].is_empty() {
+        let mut next = vec![];
+        for &p in &fronts[i] {
+            for &q in &dominated_by[p] {
+                domination_count[q] -= 1;
+                if domination_count[q] == 0 {
+                    next.push(q);
+                }
+            }
+        }
+        i += 1;
+        fronts.push(next);
+    }
+    fronts.pop();
+    fronts
+}
+
+// Crowding distance of each member of a front, in the same order as
+// `front`.  The extremes of each objective get an infinite distance so that
+// they're always kept.
+fn crowding_distance(population: &[Individual], front: &[usize]) -> Vec<f32> {
+    let mut distance = vec![0.0; front.len()];
+    if front.len() <= 2 {
+        return vec![f32::INFINITY; front.len()];
+    }
+
+    let objectives: [fn(&Individual) -> f32; 2] = [|ind| ind.accuracy, |ind| ind.states as f32];
+    for objective in objectives.iter() {
+        let mut order: Vec<usize> = (0..front.len()).collect();
+        order.sort_by(|&a, &b| {
+            objective(&population[front[a]])
+                .partial_cmp(&objective(&population[front[b]]))
+                .unwrap_or(Ordering::Equal)
+        });
+        let lowest = objective(&population[front[order[0]]]);
+        let highest = objective(&population[front[order[order.len() - 1]]]);
+        distance[order[0]] = f32::INFINITY;
+        distance[order[order.len() - 1]] = f32::INFINITY;
+        if highest == lowest {
+            continue;
+        }
+        for k in 1..order.len() - 1 {
+            let prev = objective(&population[front[order[k - 1]]]);
+            let next = objective(&population[front[order[k + 1]]]);
+            distance[order[k]] += (next - prev) / (highest - lowest);
+        }
+    }
+    distance
+}
+
+// Rank and crowding distance for every member of the population.
+fn rank_population(population: &[Individual]) -> (Vec<usize>, Vec<f32>) {
+    let mut rank = vec![0; population.len()];
+    let mut crowding = vec![0.0; population.len()];
+    for (i, front) in non_dominated_sort(population).iter().enumerate() {
+        for (&member, distance) in front.iter().zip(crowding_distance(population, front)) {
+            rank[member] = i;
+            crowding[member] = distance;
+        }
+    }
+    (rank, crowding)
+}
+
+// Binary tournament: prefer the lower rank, breaking ties by the larger
+// crowding distance.
+fn tournament<R: Rng>(rank: &[usize], crowding: &[f32], rng: &mut R) -> usize {
+    let a = rng.gen_range(0, rank.len());
+    let b = rng.gen_range(0, rank.len());
+    if rank[a] < rank[b] || (rank[a] == rank[b] && crowding[a] >= crowding[b]) {
+        a
+    } else {
+        b
+    }
+}
+
+// Pick the best `size` members of a combined parent+offspring population,
+// filling from the best fronts and using crowding distance to choose within
+// the last front that fits.
+fn select_survivors(combined: Vec<Individual>, size: usize) -> Vec<Individual> {
+    let mut chosen = vec![];
+    for front in non_dominated_sort(&combined) {
+        if chosen.len() + front.len() <= size {
+            chosen.extend(front);
+        } else {
+            let distance = crowding_distance(&combined, &front);
+            let mut order: Vec<usize> = (0..front.len()).collect();
+            order.sort_by(|&a, &b| {
+                distance[b]
+                    .partial_cmp(&distance[a])
+                    .unwrap_or(Ordering::Equal)
+            });
+            let remaining = size - chosen.len();
+            chosen.extend(order.iter().take(remaining).map(|&k| front[k]));
+            break;
+        }
+    }
+    chosen.iter().map(|&i| combined[i].clone()).collect()
+}
+
+// Run NSGA-II and return the final Pareto front, without duplicate
+// chromosomes and sorted by state count.
+pub fn evolve<R: Rng>(params: &ParetoParams, rng: &mut R) -> Vec<Individual> {
+    let mut population: Vec<Individual> = (0..params.population_size)
+        .map(|_| {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(params.num_states, rng);
+            Individual::evaluate(flib.as_chromosome(), &params.environment)
+        })
+        .collect();
+
+    for _generation in 0..params.generations {
+        let (rank, crowding) = rank_population(&population);
+        let mut offspring = vec![];
+        for _ in 0..params.population_size {
+            let parent1 = &population[tournament(&rank, &crowding, rng)].chromosome;
+            let parent2 = &population[tournament(&rank, &crowding, rng)].chromosome;
+            let child = mutate(&random_combine(parent1, parent2, rng), rng);
+            offspring.push(Individual::evaluate(child, &params.environment));
+        }
+
+        population.extend(offspring);
+        population = select_survivors(population, params.population_size);
+    }
+
+    let mut front: Vec<Individual> = non_dominated_sort(&population)[0]
+        .iter()
+        .map(|&i| population[i].clone())
+        .collect();
+    front.sort_by(|a, b| {
+        a.states
+            .cmp(&b.states)
+            .then(a.chromosome.cmp(&b.chromosome))
+    });
+    front.dedup_by(|a, b| a.chromosome == b.chromosome);
+    front
+}
+
+pub fn front_as_table(front: &[Individual]) -> String {
+    let width = front
+        .iter()
+        .map(|ind| ind.chromosome.len())
+        .max()
+        .unwrap_or(0)
+        .max("chromosome".len());
+    let mut table = format!(
+        "{:<w$}  {:>8}  {:>6}\n",
+        "chromosome",
+        "accuracy",
+        "states",
+        w = width
+    );
+    for ind in front {
+        table.push_str(&format!(
+            "{:<w$}  {:>8.3}  {:>6}\n",
+            ind.chromosome,
+            ind.accuracy,
+            ind.states,
+            w = width
+        ));
+    }
+    table
+}
+
+#[derive(Serialize)]
+struct FrontReport<'a> {
+    environment: &'a str,
+    front: &'a [Individual],
+}
+
+pub fn front_as_json(environment: &str, front: &[Individual]) -> String {
+    serde_json::to_string_pretty(&FrontReport { environment, front })
+        .expect("Failed to serialize Pareto front")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeded_rng;
+
+    fn individual(accuracy: f32, states: usize) -> Individual {
+        Individual {
+            chromosome: String::new(),
+            accuracy,
+            states,
+        }
+    }
+
+    #[test]
+    fn test_dominates() {
+        assert!(individual(0.9, 2).dominates(&individual(0.8, 2)));
+        assert!(individual(0.9, 2).dominates(&individual(0.9, 3)));
+        assert!(!individual(0.9, 2).dominates(&individual(0.9, 2)));
+        assert!(!individual(0.9, 3).dominates(&individual(0.8, 2)));
+    }
+
+    #[test]
+    fn test_non_dominated_sort() {
+        let population = vec![
+            individual(1.0, 4),
+            individual(0.5, 1),
+            individual(0.5, 4),
+            individual(0.75, 2),
+            individual(0.25, 4),
+        ];
+        let fronts = non_dominated_sort(&population);
+        assert_eq!(fronts, vec![vec![0, 1, 3], vec![2], vec![4]]);
+    }
+
+    #[test]
+    fn test_crowding_distance_keeps_extremes() {
+        let population = vec![individual(1.0, 4), individual(0.75, 2), individual(0.5, 1)];
+        let distance = crowding_distance(&population, &[0, 1, 2]);
+        assert!(distance[0].is_infinite());
+        assert!(distance[2].is_infinite());
+        assert!(distance[1].is_finite() && distance[1] > 0.0);
+    }
+
+    #[test]
+    fn test_evolve_returns_front() {
+        let params = ParetoParams {
+            environment: String::from("011001"),
+            population_size: 12,
+            generations: 20,
+            num_states: 4,
+        };
+        let front = evolve(&params, &mut seeded_rng(1));
+        assert!(!front.is_empty());
+        for a in &front {
+            assert!(front.iter().all(|b| !b.dominates(a)));
+        }
+    }
+
+    #[test]
+    fn test_evolve_is_reproducible() {
+        let params = ParetoParams {
+            environment: String::from("0110010111"),
+            population_size: 10,
+            generations: 10,
+            num_states: 4,
+        };
+        let chromosomes = |front: Vec<Individual>| -> Vec<String> {
+            front.into_iter().map(|ind| ind.chromosome).collect()
+        };
+        assert_eq!(
+            chromosomes(evolve(&params, &mut seeded_rng(7))),
+            chromosomes(evolve(&params, &mut seeded_rng(7)))
+        );
+    }
+}
@@ -0,0 +1,152 @@
+// Property-based tests of flib invariants and the genetic operators, run
+// over randomly generated chromosomes and environments.
+
+use proptest::collection::vec;
+use proptest::prelude::*;
+use rand::{SeedableRng, StdRng};
+
+use crate::{
+    make_from_chromosome, mutate, mutate_at_index, parse_chromosome, random_combine, state_to_char,
+    uniform_combine, Horizon, MAX_STATES,
+};
+
+fn chromosome_from(genes: &[(u8, usize)]) -> String {
+    let mut chromosome = String::new();
+    for &(output, dest) in genes {
+        chromosome.push((b'0' + output) as char);
+        chromosome.push(state_to_char(dest));
+    }
+    chromosome
+}
+
+fn genes(num_states: usize) -> impl Strategy<Value = Vec<(u8, usize)>> {
+    vec((0..2u8, 0..num_states), num_states * 2)
+}
+
+fn chromosome() -> impl Strategy<Value = String> {
+    (1..=MAX_STATES)
+        .prop_flat_map(genes)
+        .prop_map(|genes| chromosome_from(&genes))
+}
+
+// Two chromosomes with the same number of states.
+fn parents() -> impl Strategy<Value = (String, String)> {
+    (1..=MAX_STATES).prop_flat_map(|n| {
+        (genes(n), genes(n)).prop_map(|(a, b)| (chromosome_from(&a), chromosome_from(&b)))
+    })
+}
+
+fn environment() -> impl Strategy<Value = String> {
+    "[01]{1,40}"
+}
+
+fn horizon() -> impl Strategy<Value = Horizon> {
+    prop_oneof![
+        (1..8usize).prop_map(Horizon::Ahead),
+        (1..5usize).prop_map(Horizon::Window),
+    ]
+}
+
+// A chromosome together with a renumbering of its states.  State 0 is
+// where every flib starts, so it keeps its number.
+fn relabelled() -> impl Strategy<Value = (String, Vec<usize>)> {
+    (1..=MAX_STATES).prop_flat_map(|n| {
+        let others: Vec<usize> = (1..n).collect();
+        (genes(n), Just(others).prop_shuffle()).prop_map(|(genes, others)| {
+            let mut mapping = vec![0];
+            mapping.extend(others);
+            (chromosome_from(&genes), mapping)
+        })
+    })
+}
+
+fn relabel(chromosome: &str, mapping: &[usize]) -> String {
+    let flib = make_from_chromosome(chromosome.to_string());
+    let mut states = vec![vec![]; mapping.len()];
+    for (state, transitions) in flib.states.iter().enumerate() {
+        states[mapping[state]] = transitions
+            .iter()
+            .map(|&(output, dest)| (output, mapping[dest]))
+            .collect();
+    }
+    let genes: Vec<(u8, usize)> = states
+        .iter()
+        .flatten()
+        .map(|&(output, dest)| (output as u8 - b'0', dest))
+        .collect();
+    chromosome_from(&genes)
+}
+
+fn differences(a: &str, b: &str) -> Vec<usize> {
+    a.chars()
+        .zip(b.chars())
+        .enumerate()
+        .filter(|(_, (x, y))| x != y)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+proptest! {
+    #[test]
+    fn chromosomes_round_trip(chromosome in chromosome()) {
+        let flib = parse_chromosome(&chromosome).unwrap();
+        prop_assert_eq!(flib.as_chromosome(), chromosome.clone());
+        prop_assert_eq!(make_from_chromosome(chromosome.clone()).as_chromosome(), chromosome);
+    }
+
+    #[test]
+    fn mutation_changes_only_its_gene(chromosome in chromosome(), position in any::<prop::sample::Index>(), seed: usize) {
+        let mut rng = StdRng::from_seed(&[seed][..]);
+        let position = position.index(chromosome.len());
+        let mutant = mutate_at_index(&chromosome, position, &mut rng);
+        prop_assert!(parse_chromosome(&mutant).is_ok());
+        let changed = differences(&chromosome, &mutant);
+        if position % 2 == 0 {
+            // Outputs always flip.
+            prop_assert_eq!(changed, vec![position]);
+        } else {
+            // A transition may be redrawn as the state it already had.
+            prop_assert!(changed.is_empty() || changed == vec![position]);
+        }
+
+        let mutant = mutate(&chromosome, &mut rng);
+        prop_assert!(parse_chromosome(&mutant).is_ok());
+        prop_assert!(differences(&chromosome, &mutant).len() <= 1);
+    }
+
+    #[test]
+    fn crossover_keeps_parents_genes((parent1, parent2) in parents(), seed: usize) {
+        let mut rng = StdRng::from_seed(&[seed][..]);
+        for child in [
+            random_combine(&parent1, &parent2, &mut rng),
+            uniform_combine(&parent1, &parent2, &mut rng),
+        ] {
+            prop_assert_eq!(child.len(), parent1.len());
+            prop_assert!(parse_chromosome(&child).is_ok());
+            for ((ch, ch1), ch2) in child.chars().zip(parent1.chars()).zip(parent2.chars()) {
+                prop_assert!(ch == ch1 || ch == ch2);
+            }
+        }
+    }
+
+    #[test]
+    fn scores_are_fractions(chromosome in chromosome(), environment in environment(), horizon in horizon()) {
+        let mut flib = make_from_chromosome(chromosome);
+        let score = flib.predict_horizon(&environment, horizon);
+        prop_assert!((0.0..=1.0).contains(&score));
+        let score = flib.predict(&environment);
+        prop_assert!((0.0..=1.0).contains(&score));
+    }
+
+    #[test]
+    fn relabelling_keeps_scores((chromosome, mapping) in relabelled(), environment in environment(), horizon in horizon()) {
+        let renamed = relabel(&chromosome, &mapping);
+        let mut original = make_from_chromosome(chromosome);
+        let mut copy = make_from_chromosome(renamed);
+        prop_assert_eq!(
+            original.predict_horizon(&environment, horizon),
+            copy.predict_horizon(&environment, horizon)
+        );
+        prop_assert_eq!(original.canonical_chromosome(), copy.canonical_chromosome());
+    }
+}
@@ -0,0 +1,791 @@
+// A local HTTP/JSON API for queueing simulation runs from scripts.
+//
+//     POST   /jobs                  submit a run configuration
+//     GET    /jobs                  list the jobs and their status
+//     GET    /jobs/ID               one job's status and finished runs
+//     GET    /jobs/ID/stats?since=N per-generation statistics, from the
+//                                   Nth record on, or the oldest kept
+//     GET    /jobs/ID/best          the best chromosome found so far
+//     DELETE /jobs/ID               cancel a queued or running job
+//
+// A submitted configuration is a JSON config file, as for `simulate
+// --config`, and gets one run per environment.  The server doesn't write
+// files, so the [output] settings aren't accepted.  Every run stops after
+// at most MAX_GENERATIONS generations, which is also the default, and a
+// job keeps only its last STATS_WINDOW statistics records.  Populations
+// and comma or plus offspring counts are limited too, so one job can't
+// take all the memory.  Jobs wait in a bounded queue for one of a fixed
+// number of worker threads; when the queue is full, submissions are
+// refused with 503.  A job whose run panics is marked failed, and its
+// worker goes on to the next job.  Once more than KEPT_JOBS jobs have
+// ended, the oldest ended ones are forgotten, and asking about them gives
+// 404; IDs are never reused.
+//
+// The HTTP support is only what a local client needs: one request per
+// connection, with the body sized by Content-Length.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use serde::Serialize;
+use serde_json::{json, Value};
+
+use crate::config::{Config, OutputConfig};
+use crate::{diversity, find_minmax, make_rng, simulate, Control, Observer, Replacement, Report};
+
+// Largest request body accepted.
+const MAX_BODY: usize = 1 << 20;
+
+// Most generations a submitted run may take, so that every job ends.
+const MAX_GENERATIONS: usize = 1_000_000;
+
+// Largest population, and most offspring per generation for comma and
+// plus replacement, that a submitted run may have.
+const MAX_POPULATION: usize = 100_000;
+const MAX_LAMBDA: usize = 100_000;
+
+// Statistics records kept for each job; older ones are dropped.
+const STATS_WINDOW: usize = 10_000;
+
+// Jobs that have ended which are kept for clients to ask about.
+const KEPT_JOBS: usize = 100;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Queued,
+    Running,
+    Finished,
+    Cancelled,
+    Failed,
+}
+
+impl Status {
+    fn has_ended(self) -> bool {
+        !matches!(self, Status::Queued | Status::Running)
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct GenerationStats {
+    // Index of the run, and so of the environment, within the job.
+    run: usize,
+    generation: usize,
+    best: f32,
+    mean: f32,
+    diversity: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct RunSummary {
+    environment: String,
+    generations: usize,
+    solution: Option<String>,
+    best: String,
+    best_score: f32,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct Best {
+    run: usize,
+    generation: usize,
+    chromosome: String,
+    score: f32,
+}
+
+// What a job has done so far.  Each job has its own lock on this, so a
+// worker recording a generation doesn't hold up requests about other jobs.
+#[derive(Default)]
+struct Progress {
+    // The most recent statistics records, and how many older ones were
+    // dropped to make room for them.
+    stats: VecDeque<GenerationStats>,
+    dropped: usize,
+    runs: Vec<RunSummary>,
+    best: Option<Best>,
+}
+
+impl Progress {
+    // Number of statistics records ever made.
+    fn recorded(&self) -> usize {
+        self.dropped + self.stats.len()
+    }
+
+    fn record(&mut self, stats: GenerationStats) {
+        if self.stats.len() == STATS_WINDOW {
+            self.stats.pop_front();
+            self.dropped += 1;
+        }
+        self.stats.push_back(stats);
+    }
+}
+
+struct Job {
+    config: Config,
+    status: Status,
+    // Why the job failed, if it did.
+    error: Option<String>,
+    cancel: Arc<AtomicBool>,
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl Job {
+    fn new(config: Config) -> Job {
+        Job {
+            config,
+            status: Status::Queued,
+            error: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+            progress: Arc::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct JobTable {
+    // Jobs by ID, which is also the order they were submitted in.
+    jobs: BTreeMap<usize, Job>,
+    next_id: usize,
+}
+
+impl JobTable {
+    // Forget the oldest jobs that have ended, keeping KEPT_JOBS of them.
+    fn evict(&mut self) {
+        let ended: Vec<usize> = self
+            .jobs
+            .iter()
+            .filter(|(_, job)| job.status.has_ended())
+            .map(|(&id, _)| id)
+            .collect();
+        for id in &ended[..ended.len().saturating_sub(KEPT_JOBS)] {
+            self.jobs.remove(id);
+        }
+    }
+}
+
+type Jobs = Mutex<JobTable>;
+
+fn no_job(id: impl std::fmt::Display) -> (u16, Value) {
+    (404, json!({ "error": format!("no job {}", id) }))
+}
+
+// Records a running job's progress, and stops it once it's cancelled.
+struct JobObserver<'a> {
+    progress: &'a Mutex<Progress>,
+    run: usize,
+    cancel: &'a AtomicBool,
+}
+
+impl<'a> Observer for JobObserver<'a> {
+    fn generation(&mut self, report: &Report) -> Control {
+        let (_, best) = find_minmax(report.scores);
+        let chromosomes: Vec<String> = report
+            .population
+            .iter()
+            .map(|f| f.as_chromosome())
+            .collect();
+        // Everything is worked out before taking the lock, which is then
+        // only held to store it.
+        let stats = GenerationStats {
+            run: self.run,
+            generation: report.generation,
+            best: report.scores[best],
+            mean: report.scores.iter().sum::<f32>() / (report.scores.len() as f32),
+            diversity: diversity(&chromosomes),
+        };
+
+        let mut progress = self.progress.lock().unwrap();
+        if progress.best.as_ref().is_none_or(|b| stats.best > b.score) {
+            progress.best = Some(Best {
+                run: self.run,
+                generation: report.generation,
+                chromosome: chromosomes[best].clone(),
+                score: stats.best,
+            });
+        }
+        progress.record(stats);
+        drop(progress);
+        if self.cancel.load(Ordering::Relaxed) {
+            Control::Stop
+        } else {
+            Control::Continue
+        }
+    }
+}
+
+fn run_job(progress: &Mutex<Progress>, config: &Config, cancel: &AtomicBool) {
+    let mut rng = make_rng(config.seed).expect("Failed to seed random number generator");
+    for (run, params) in config.params().iter().enumerate() {
+        if cancel.load(Ordering::Relaxed) {
+            return;
+        }
+        let mut observer = JobObserver {
+            progress,
+            run,
+            cancel,
+        };
+        let outcome = simulate(params, &mut rng, &mut observer);
+        progress.lock().unwrap().runs.push(RunSummary {
+            environment: params.environment.clone(),
+            generations: outcome.generation,
+            solution: outcome.solution,
+            best: outcome.best,
+            best_score: outcome.best_score,
+        });
+    }
+}
+
+fn work(jobs: Arc<Jobs>, queue: Arc<Mutex<Receiver<usize>>>) {
+    loop {
+        // The lock is only held while waiting, so one idle worker waits
+        // for the next job while the others are busy.
+        let id = match queue.lock().unwrap().recv() {
+            Ok(id) => id,
+            Err(_) => return,
+        };
+        let (config, cancel, progress) = {
+            let mut jobs = jobs.lock().unwrap();
+            let job = match jobs.jobs.get_mut(&id) {
+                // Cancelled while it was queued, and maybe forgotten since.
+                Some(job) if job.status != Status::Cancelled => job,
+                _ => continue,
+            };
+            job.status = Status::Running;
+            (job.config.clone(), job.cancel.clone(), job.progress.clone())
+        };
+        let (status, error) = run_to_end(|| run_job(&progress, &config, &cancel), &cancel);
+        let mut jobs = jobs.lock().unwrap();
+        let job = jobs.jobs.get_mut(&id).expect("running job was forgotten");
+        job.status = status;
+        job.error = error;
+        jobs.evict();
+    }
+}
+
+// Run a job, returning how it ended and, if it panicked, why.
+fn run_to_end<F: FnOnce()>(run: F, cancel: &AtomicBool) -> (Status, Option<String>) {
+    match panic::catch_unwind(AssertUnwindSafe(run)) {
+        Ok(()) if cancel.load(Ordering::Relaxed) => (Status::Cancelled, None),
+        Ok(()) => (Status::Finished, None),
+        Err(payload) => {
+            let message = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| String::from("unknown error"));
+            (
+                Status::Failed,
+                Some(format!("the run panicked: {}", message)),
+            )
+        }
+    }
+}
+
+pub struct Server {
+    listener: TcpListener,
+    jobs: Arc<Jobs>,
+    queue: SyncSender<usize>,
+}
+
+impl Server {
+    // Listen on `address` and start `workers` worker threads.  At most
+    // `queue_size` jobs can wait for a worker.
+    pub fn bind(address: &str, workers: usize, queue_size: usize) -> io::Result<Server> {
+        let listener = TcpListener::bind(address)?;
+        let jobs = Arc::new(Mutex::new(JobTable::default()));
+        let (queue, receiver) = sync_channel(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        for _ in 0..workers {
+            let jobs = Arc::clone(&jobs);
+            let receiver = Arc::clone(&receiver);
+            thread::spawn(move || work(jobs, receiver));
+        }
+        Ok(Server {
+            listener,
+            jobs,
+            queue,
+        })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    // Answer requests until the listener fails.  A connection that goes
+    // wrong is dropped without affecting the others.
+    pub fn run(&self) -> io::Result<()> {
+        for stream in self.listener.incoming() {
+            if let Err(e) = self.answer(stream?) {
+                eprintln!("Error: {}", e);
+            }
+        }
+        Ok(())
+    }
+
+    fn answer(&self, stream: TcpStream) -> io::Result<()> {
+        // Requests are answered one at a time, so don't let a client that
+        // stops sending hold up the rest.
+        stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let (status, body) = match read_request(&mut reader) {
+            Ok((method, path, body)) => self.handle(&method, &path, &body),
+            Err(e) => (400, json!({ "error": e })),
+        };
+        write_response(stream, status, &body)
+    }
+
+    // Route a request, returning the HTTP status and JSON body.
+    pub fn handle(&self, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        match (method, &segments[..]) {
+            ("POST", ["jobs"]) => self.submit(body),
+            ("GET", ["jobs"]) => self.list(),
+            (_, ["jobs", id, rest @ ..]) => {
+                let id = match id.parse::<usize>() {
+                    Ok(id) => id,
+                    Err(_) => return no_job(id),
+                };
+                match (method, rest) {
+                    ("GET", []) => self.status(id),
+                    ("DELETE", []) => self.cancel(id),
+                    ("GET", ["stats"]) => self.stats(id, query),
+                    ("GET", ["best"]) => self.best(id),
+                    _ => (405, json!({ "error": "method not allowed" })),
+                }
+            }
+            (_, ["jobs"]) => (405, json!({ "error": "method not allowed" })),
+            _ => (404, json!({ "error": format!("no such path {}", path) })),
+        }
+    }
+
+    fn submit(&self, body: &[u8]) -> (u16, Value) {
+        let mut config: Config = match serde_json::from_slice(body) {
+            Ok(config) => config,
+            Err(e) => return (400, json!({ "error": format!("invalid config: {}", e) })),
+        };
+        match config.stop.max_generations {
+            Some(n) if n > MAX_GENERATIONS => {
+                let error = format!(
+                    "max_generations {} is more than the server's limit of {}",
+                    n, MAX_GENERATIONS
+                );
+                return (400, json!({ "error": error }));
+            }
+            Some(_) => {}
+            None => config.stop.max_generations = Some(MAX_GENERATIONS),
+        }
+        if let Some(population) = config.population.filter(|&n| n > MAX_POPULATION) {
+            let error = format!(
+                "population {} is more than the server's limit of {}",
+                population, MAX_POPULATION
+            );
+            return (400, json!({ "error": error }));
+        }
+        if let Some(Replacement::Comma(lambda)) | Some(Replacement::Plus(lambda)) =
+            config.replacement
+        {
+            if lambda > MAX_LAMBDA {
+                let error = format!(
+                    "{} offspring are more than the server's limit of {}",
+                    lambda, MAX_LAMBDA
+                );
+                return (400, json!({ "error": error }));
+            }
+        }
+        if let Err(e) = config.validate() {
+            return (400, json!({ "error": e }));
+        }
+        if config.output != OutputConfig::default() {
+            return (
+                400,
+                json!({ "error": "output settings aren't supported by the server" }),
+            );
+        }
+
+        // Hold the lock until the job is queued, so no worker can look
+        // for it before it's there.
+        let mut jobs = self.jobs.lock().unwrap();
+        let id = jobs.next_id;
+        match self.queue.try_send(id) {
+            Ok(()) => {}
+            Err(TrySendError::Full(_)) => return (503, json!({ "error": "job queue is full" })),
+            Err(TrySendError::Disconnected(_)) => {
+                return (503, json!({ "error": "no workers are running" }))
+            }
+        }
+        jobs.jobs.insert(id, Job::new(config));
+        jobs.next_id += 1;
+        (201, json!({ "id": id }))
+    }
+
+    fn list(&self) -> (u16, Value) {
+        let jobs = self.jobs.lock().unwrap();
+        let list: Vec<Value> = jobs
+            .jobs
+            .iter()
+            .map(|(id, job)| json!({ "id": id, "status": job.status }))
+            .collect();
+        (200, json!(list))
+    }
+
+    fn status(&self, id: usize) -> (u16, Value) {
+        let jobs = self.jobs.lock().unwrap();
+        let job = match jobs.jobs.get(&id) {
+            Some(job) => job,
+            None => return no_job(id),
+        };
+        let progress = job.progress.lock().unwrap();
+        let mut body = json!({
+            "id": id,
+            "status": job.status,
+            "config": job.config,
+            "generations": progress.recorded(),
+            "runs": progress.runs,
+        });
+        if let Some(error) = &job.error {
+            body["error"] = json!(error);
+        }
+        (200, body)
+    }
+
+    fn stats(&self, id: usize, query: &str) -> (u16, Value) {
+        let since = match query.split('&').find_map(|p| p.strip_prefix("since=")) {
+            Some(n) => match n.parse::<usize>() {
+                Ok(n) => n,
+                Err(_) => return (400, json!({ "error": format!("invalid since '{}'", n) })),
+            },
+            None => 0,
+        };
+        let (status, progress) = match self.jobs.lock().unwrap().jobs.get(&id) {
+            Some(job) => (job.status, job.progress.clone()),
+            None => return no_job(id),
+        };
+        let progress = progress.lock().unwrap();
+        // Records before `first` have been dropped, so a client asking for
+        // them gets the oldest ones still kept.
+        let first = progress.dropped;
+        let stats: Vec<&GenerationStats> = progress
+            .stats
+            .iter()
+            .skip(since.saturating_sub(first))
+            .collect();
+        let body = json!({
+            "status": status,
+            "first": first,
+            "next": progress.recorded(),
+            "stats": stats,
+        });
+        (200, body)
+    }
+
+    fn best(&self, id: usize) -> (u16, Value) {
+        let progress = match self.jobs.lock().unwrap().jobs.get(&id) {
+            Some(job) => job.progress.clone(),
+            None => return no_job(id),
+        };
+        let best = progress.lock().unwrap().best.clone();
+        match best {
+            Some(best) => (200, json!(best)),
+            None => (404, json!({ "error": "no generation has been scored yet" })),
+        }
+    }
+
+    fn cancel(&self, id: usize) -> (u16, Value) {
+        let mut jobs = self.jobs.lock().unwrap();
+        let job = match jobs.jobs.get_mut(&id) {
+            Some(job) => job,
+            None => return no_job(id),
+        };
+        match job.status {
+            // A worker will skip it when it comes off the queue.
+            Status::Queued => job.status = Status::Cancelled,
+            // The worker stops it after the current generation.
+            Status::Running => {}
+            Status::Finished | Status::Cancelled | Status::Failed => {
+                return (409, json!({ "error": "job has already ended" }))
+            }
+        }
+        job.cancel.store(true, Ordering::Relaxed);
+        let reply = json!({ "id": id, "status": job.status });
+        jobs.evict();
+        (202, reply)
+    }
+}
+
+// Read one request, returning its method, path and body.
+fn read_request<R: BufRead>(reader: &mut R) -> Result<(String, String, Vec<u8>), String> {
+    let mut line = String::new();
+    reader.read_line(&mut line).map_err(|e| e.to_string())?;
+    let mut parts = line.split_whitespace();
+    let (method, path) = match (parts.next(), parts.next()) {
+        (Some(method), Some(path)) => (method.to_string(), path.to_string()),
+        _ => return Err(String::from("malformed request line")),
+    };
+
+    let mut length = 0;
+    loop {
+        line.clear();
+        reader.read_line(&mut line).map_err(|e| e.to_string())?;
+        let header = line.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                length = value
+                    .trim()
+                    .parse()
+                    .map_err(|_| String::from("invalid Content-Length"))?;
+            }
+        }
+    }
+    if length > MAX_BODY {
+        return Err(String::from("request body is too large"));
+    }
+    let mut body = vec![0; length];
+    reader.read_exact(&mut body).map_err(|e| e.to_string())?;
+    Ok((method, path, body))
+}
+
+fn write_response<W: Write>(mut writer: W, status: u16, body: &Value) -> io::Result<()> {
+    let reason = match status {
+        200 => "OK",
+        201 => "Created",
+        202 => "Accepted",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        503 => "Service Unavailable",
+        _ => "",
+    };
+    let body = body.to_string();
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+         Connection: close\r\n\r\n{}",
+        status,
+        reason,
+        body.len(),
+        body
+    )?;
+    writer.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+    use std::time::Instant;
+
+    // Start a server on a free port, returning its address.
+    fn start(workers: usize, queue_size: usize) -> SocketAddr {
+        let server = Server::bind("127.0.0.1:0", workers, queue_size).unwrap();
+        let address = server.local_addr().unwrap();
+        thread::spawn(move || server.run());
+        address
+    }
+
+    // A minimal client: send one request and parse the JSON reply.
+    fn request(address: SocketAddr, method: &str, path: &str, body: &str) -> (u16, Value) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        write!(
+            stream,
+            "{} {} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\n{}",
+            method,
+            path,
+            body.len(),
+            body
+        )
+        .unwrap();
+        let mut response = String::new();
+        stream.read_to_string(&mut response).unwrap();
+        let (head, body) = response.split_once("\r\n\r\n").unwrap();
+        let status = head.split_whitespace().nth(1).unwrap().parse().unwrap();
+        (status, serde_json::from_str(body).unwrap())
+    }
+
+    // Poll a job until its status is `status`.
+    fn wait_for(address: SocketAddr, id: u64, status: &str) -> Value {
+        let start = Instant::now();
+        loop {
+            let (_, job) = request(address, "GET", &format!("/jobs/{}", id), "");
+            if job["status"] == status {
+                return job;
+            }
+            assert!(
+                start.elapsed() < Duration::from_secs(30),
+                "job {} is still {}",
+                id,
+                job["status"]
+            );
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    // A job that can't finish: one state isn't enough to predict this.
+    const ENDLESS: &str = r#"{"environments": ["0110100110010110"], "states": 1}"#;
+
+    #[test]
+    fn test_run_job() {
+        let address = start(2, 4);
+        let config = r#"{"environments": ["011001", "0011"], "seed": 44,
+                         "stop": {"max_generations": 5000}}"#;
+        let (status, reply) = request(address, "POST", "/jobs", config);
+        assert_eq!(status, 201);
+        let id = reply["id"].as_u64().unwrap();
+
+        let job = wait_for(address, id, "finished");
+        let runs = job["runs"].as_array().unwrap();
+        assert_eq!(runs.len(), 2);
+        assert_eq!(runs[1]["environment"], "0011");
+        assert!(runs.iter().all(|run| run["best_score"] == 1.0));
+
+        let (_, stats) = request(address, "GET", &format!("/jobs/{}/stats", id), "");
+        let next = stats["next"].as_u64().unwrap();
+        assert_eq!(stats["first"], 0);
+        assert_eq!(stats["stats"].as_array().unwrap().len() as u64, next);
+        assert_eq!(stats["stats"][0]["generation"], 0);
+        let path = format!("/jobs/{}/stats?since={}", id, next - 1);
+        let (_, last) = request(address, "GET", &path, "");
+        assert_eq!(last["stats"][0]["run"], 1);
+
+        let (status, best) = request(address, "GET", &format!("/jobs/{}/best", id), "");
+        assert_eq!(status, 200);
+        assert_eq!(best["score"], 1.0);
+        assert_eq!(best["chromosome"], runs[0]["best"]);
+
+        let (status, list) = request(address, "GET", "/jobs", "");
+        assert_eq!(status, 200);
+        assert_eq!(list[0]["status"], "finished");
+        let (status, _) = request(address, "DELETE", &format!("/jobs/{}", id), "");
+        assert_eq!(status, 409);
+    }
+
+    #[test]
+    fn test_bad_requests() {
+        let address = start(1, 1);
+        let (status, reply) = request(address, "POST", "/jobs", r#"{"populaton": 5}"#);
+        assert_eq!(status, 400);
+        assert!(reply["error"].as_str().unwrap().contains("populaton"));
+        let (status, _) = request(address, "POST", "/jobs", r#"{"environments": ["012"]}"#);
+        assert_eq!(status, 400);
+        let config = r#"{"output": {"stats_csv": "/tmp/stats.csv"}}"#;
+        let (status, _) = request(address, "POST", "/jobs", config);
+        assert_eq!(status, 400);
+        assert_eq!(request(address, "GET", "/jobs/7", "").0, 404);
+        assert_eq!(request(address, "GET", "/jobs/x/best", "").0, 404);
+        assert_eq!(request(address, "GET", "/nowhere", "").0, 404);
+        assert_eq!(request(address, "PUT", "/jobs", "").0, 405);
+
+        for config in &[
+            r#"{"stop": {"max_generations": 1000000000}}"#,
+            r#"{"population": 1000000000}"#,
+            r#"{"replacement": "plus:1000000000"}"#,
+            r#"{"replacement": "comma:1000000000"}"#,
+        ] {
+            let (status, reply) = request(address, "POST", "/jobs", config);
+            assert_eq!(status, 400);
+            assert!(
+                reply["error"].as_str().unwrap().contains("limit"),
+                "{}",
+                config
+            );
+        }
+    }
+
+    #[test]
+    fn test_run_to_end() {
+        let cancel = AtomicBool::new(false);
+        assert_eq!(run_to_end(|| {}, &cancel), (Status::Finished, None));
+        let (status, error) = run_to_end(|| panic!("out of states"), &cancel);
+        assert_eq!(status, Status::Failed);
+        assert!(error.unwrap().contains("out of states"));
+        cancel.store(true, Ordering::Relaxed);
+        assert_eq!(run_to_end(|| {}, &cancel), (Status::Cancelled, None));
+    }
+
+    #[test]
+    fn test_default_generation_limit() {
+        let address = start(1, 1);
+        let (_, reply) = request(address, "POST", "/jobs", ENDLESS);
+        let id = reply["id"].as_u64().unwrap();
+        let (_, job) = request(address, "GET", &format!("/jobs/{}", id), "");
+        assert_eq!(job["config"]["stop"]["max_generations"], MAX_GENERATIONS);
+        request(address, "DELETE", &format!("/jobs/{}", id), "");
+    }
+
+    #[test]
+    fn test_stats_window() {
+        let mut progress = Progress::default();
+        for generation in 0..STATS_WINDOW + 5 {
+            progress.record(GenerationStats {
+                run: 0,
+                generation,
+                best: 0.5,
+                mean: 0.5,
+                diversity: 0.0,
+            });
+        }
+        assert_eq!(progress.stats.len(), STATS_WINDOW);
+        assert_eq!(progress.dropped, 5);
+        assert_eq!(progress.recorded(), STATS_WINDOW + 5);
+        assert_eq!(progress.stats[0].generation, 5);
+    }
+
+    #[test]
+    fn test_evict_ended_jobs() {
+        let mut table = JobTable::default();
+        let statuses = [Status::Running, Status::Queued, Status::Finished];
+        for id in 0..KEPT_JOBS + 10 {
+            let mut job = Job::new(Config::default());
+            job.status = match id {
+                0 | 1 => statuses[id],
+                _ => [Status::Finished, Status::Cancelled, Status::Failed][id % 3],
+            };
+            table.jobs.insert(id, job);
+        }
+        table.evict();
+        // Jobs that haven't ended are kept however old they are, and the
+        // rest keep their IDs.
+        assert_eq!(table.jobs.len(), KEPT_JOBS + 2);
+        let ids: Vec<usize> = table.jobs.keys().cloned().collect();
+        assert_eq!(ids[..3], [0, 1, 10]);
+        assert_eq!(*ids.last().unwrap(), KEPT_JOBS + 9);
+    }
+
+    #[test]
+    fn test_cancel_and_bounded_queue() {
+        let address = start(1, 1);
+        let (_, reply) = request(address, "POST", "/jobs", ENDLESS);
+        let running = reply["id"].as_u64().unwrap();
+        wait_for(address, running, "running");
+
+        // The only worker is busy, so one job can wait and the next is
+        // refused.
+        let (status, reply) = request(address, "POST", "/jobs", ENDLESS);
+        assert_eq!(status, 201);
+        let queued = reply["id"].as_u64().unwrap();
+        let (status, _) = request(address, "POST", "/jobs", ENDLESS);
+        assert_eq!(status, 503);
+
+        let (status, reply) = request(address, "DELETE", &format!("/jobs/{}", queued), "");
+        assert_eq!(status, 202);
+        assert_eq!(reply["status"], "cancelled");
+        let (status, _) = request(address, "DELETE", &format!("/jobs/{}", running), "");
+        assert_eq!(status, 202);
+        let job = wait_for(address, running, "cancelled");
+        assert!(job["generations"].as_u64().unwrap() > 0);
+        let (status, best) = request(address, "GET", &format!("/jobs/{}/best", running), "");
+        assert_eq!(status, 200);
+        assert!(best["score"].as_f64().unwrap() < 1.0);
+    }
+}
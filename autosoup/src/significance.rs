@@ -0,0 +1,226 @@
+// Is an evolved score better than chance?
+//
+// A flib scoring 0.67 on a six-symbol environment may just be lucky.  To
+// tell, score many random flibs with the same number of states, which
+// gives the distribution of scores under the null hypothesis that the
+// flib is no better than a random machine, and see how far into its tail
+// the evolved score falls.  The report also compares the flib with simple
+// baseline predictors.
+
+use std::fmt;
+
+use rand::Rng;
+
+use crate::compiled::Scorer;
+use crate::{make_from_chromosome, Flib, Horizon};
+
+#[derive(Debug)]
+pub struct Significance {
+    pub score: f32,
+    pub samples: usize,
+    pub mean: f64,
+    pub std_dev: f64,
+    // 5th, 50th and 95th percentiles of the random flibs' scores.
+    pub percentiles: [f32; 3],
+    // Chance of a random flib scoring at least `score`.
+    pub p_value: f64,
+    pub baselines: Vec<(&'static str, f32)>,
+}
+
+// Score `samples` random flibs with `num_states` states.
+pub fn random_scores<R: Rng>(
+    environment: &str,
+    horizon: Horizon,
+    num_states: usize,
+    samples: usize,
+    rng: &mut R,
+) -> Vec<f32> {
+    let mut population: Vec<Flib> = (0..samples)
+        .map(|_| {
+            let mut flib = make_from_chromosome(String::new());
+            flib.randomize(num_states, rng);
+            flib
+        })
+        .collect();
+    match Scorer::with_horizon(environment, horizon) {
+        Some(mut scorer) => {
+            let mut scores = vec![];
+            scorer.score_population(&population, &mut scores);
+            scores
+        }
+        None => crate::score_population(&mut population, environment, horizon),
+    }
+}
+
+// Monte Carlo p-value of `score` against the sampled null distribution.
+// Counting the observed score as one more sample keeps it above zero, as
+// a finite sample can't rule anything out completely.
+pub fn p_value(score: f32, null: &[f32]) -> f64 {
+    let at_least = null.iter().filter(|&&s| s >= score).count();
+    ((at_least + 1) as f64) / ((null.len() + 1) as f64)
+}
+
+fn percentile(sorted: &[f32], fraction: f64) -> f32 {
+    let index = ((sorted.len() - 1) as f64 * fraction).round() as usize;
+    sorted[index]
+}
+
+// Score a predictor that isn't a flib.  `predict` is given each input
+// symbol in turn, over two copies of the environment as in predict(), and
+// returns its forecast of the next one.  Every baseline here would keep
+// making the same forecast if fed its own predictions, so under a window
+// horizon that forecast is compared with each symbol in the window.
+fn baseline_score<F: FnMut(char) -> char>(
+    environment: &str,
+    horizon: Horizon,
+    mut predict: F,
+) -> f32 {
+    let env: Vec<char> = environment.chars().collect();
+    let n = env.len();
+    let (offsets, per_step) = match horizon {
+        Horizon::Ahead(k) => (k..=k, 1),
+        Horizon::Window(w) => (1..=w, w),
+    };
+    let mut matches = 0;
+    for i in 0..2 * n {
+        let forecast = predict(env[i % n]);
+        matches += offsets
+            .clone()
+            .filter(|&k| forecast == env[(i + k) % n])
+            .count();
+    }
+    (matches as f32) / ((2 * n * per_step) as f32)
+}
+
+// Scores of the baseline predictors: always 0, always 1, echoing the
+// input, and predicting whichever symbol has been seen most often so far
+// (the latest input breaks ties).
+pub fn baselines(environment: &str, horizon: Horizon) -> Vec<(&'static str, f32)> {
+    let mut counts = [0, 0];
+    let majority = move |input: char| {
+        counts[(input == '1') as usize] += 1;
+        match counts[1].cmp(&counts[0]) {
+            std::cmp::Ordering::Greater => '1',
+            std::cmp::Ordering::Less => '0',
+            std::cmp::Ordering::Equal => input,
+        }
+    };
+    vec![
+        ("constant 0", baseline_score(environment, horizon, |_| '0')),
+        ("constant 1", baseline_score(environment, horizon, |_| '1')),
+        ("echo", baseline_score(environment, horizon, |input| input)),
+        ("majority", baseline_score(environment, horizon, majority)),
+    ]
+}
+
+// Assess the flib with chromosome `chromosome` against `samples` random
+// flibs of the same size.
+pub fn assess<R: Rng>(
+    chromosome: &str,
+    environment: &str,
+    horizon: Horizon,
+    samples: usize,
+    rng: &mut R,
+) -> Significance {
+    let mut flib = make_from_chromosome(chromosome.to_string());
+    let score = flib.predict_horizon(environment, horizon);
+    let mut null = random_scores(environment, horizon, flib.states.len(), samples, rng);
+
+    let mean = null.iter().map(|&s| f64::from(s)).sum::<f64>() / (samples as f64);
+    let variance = null
+        .iter()
+        .map(|&s| (f64::from(s) - mean).powi(2))
+        .sum::<f64>()
+        / ((samples.max(2) - 1) as f64);
+    let p = p_value(score, &null);
+    null.sort_by(|a, b| a.partial_cmp(b).unwrap());
+
+    Significance {
+        score,
+        samples,
+        mean,
+        std_dev: variance.sqrt(),
+        percentiles: [
+            percentile(&null, 0.05),
+            percentile(&null, 0.5),
+            percentile(&null, 0.95),
+        ],
+        p_value: p,
+        baselines: baselines(environment, horizon),
+    }
+}
+
+impl fmt::Display for Significance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Score: {:.4}", self.score)?;
+        writeln!(
+            f,
+            "Random flibs ({} samples): mean {:.4}, sd {:.4}, 5%/50%/95% {:.4}/{:.4}/{:.4}",
+            self.samples,
+            self.mean,
+            self.std_dev,
+            self.percentiles[0],
+            self.percentiles[1],
+            self.percentiles[2]
+        )?;
+        writeln!(f, "p-value: {:.4}", self.p_value)?;
+        for (name, score) in &self.baselines {
+            writeln!(f, "Baseline {}: {:.4}", name, score)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn test_p_value() {
+        let null = [0.1, 0.2, 0.5, 0.5, 0.9];
+        assert_eq!(p_value(0.5, &null), 4.0 / 6.0);
+        assert_eq!(p_value(1.0, &null), 1.0 / 6.0);
+        assert_eq!(p_value(0.0, &null), 1.0);
+    }
+
+    #[test]
+    fn test_baselines() {
+        let scores = baselines("011001", Horizon::default());
+        assert_eq!(scores[0], ("constant 0", 0.5));
+        assert_eq!(scores[1], ("constant 1", 0.5));
+        // The echo matches an echo flib exactly.
+        let mut echo = make_from_chromosome(String::from("0A1A"));
+        assert_eq!(scores[2], ("echo", echo.predict("011001")));
+        // Majority over 011001011001 forecasts 0,1,1,0,0,1,0,1,1,0,0,1
+        // against 1,1,0,0,1,0,1,1,0,0,1,0, getting 4 right.
+        assert_eq!(scores[3], ("majority", 4.0 / 12.0));
+
+        // Two steps ahead, the echo is right whenever a symbol repeats
+        // two places later.
+        let scores = baselines("0101", Horizon::Ahead(2));
+        assert_eq!(scores[2], ("echo", 1.0));
+        let scores = baselines("0011", Horizon::Window(2));
+        assert_eq!(scores[0], ("constant 0", 0.5));
+    }
+
+    #[test]
+    fn test_assess() {
+        let mut rng = StdRng::from_seed(&[38][..]);
+        // A perfect predictor for 011001 is very unlikely by chance.
+        let report = assess(
+            "1C0B0C1A1D1A0D0A",
+            "011001",
+            Horizon::default(),
+            2000,
+            &mut rng,
+        );
+        assert_eq!(report.score, 1.0);
+        assert!(report.p_value < 0.05);
+        assert!(report.mean < 0.75);
+        assert!(report.percentiles[0] <= report.percentiles[2]);
+        // Constant 0 is no better than a random flib.
+        let report = assess("0A0A", "011001", Horizon::default(), 2000, &mut rng);
+        assert!(report.p_value > 0.05);
+    }
+}
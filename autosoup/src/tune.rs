@@ -0,0 +1,304 @@
+// Hyperparameter tuning: search for the population size and operators
+// that let simulate() find perfect predictors in the fewest generations.
+//
+// Each candidate is a Config, so the winner can be written out and used
+// as-is with `simulate --config`.  A candidate's cost is its mean number
+// of generations over seeded runs on every environment, with runs that
+// give up counted as taking max_generations.
+//
+// Random search runs every candidate with every seed.  Successive halving
+// starts with a few seeds per candidate and then repeatedly drops the
+// worse half of the candidates and gives the survivors twice as many
+// seeds, so most of the runs go to the promising settings.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::config::{Config, OperatorsConfig, StopConfig};
+use crate::experiment::run_once;
+use crate::{Crossover, Selection};
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Method {
+    Random,
+    Halving,
+}
+
+impl FromStr for Method {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Method, String> {
+        match s {
+            "random" => Ok(Method::Random),
+            "halving" => Ok(Method::Halving),
+            _ => Err(format!(
+                "unknown tuning method '{}'; expected random or halving",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Method {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Method::Random => write!(f, "random"),
+            Method::Halving => write!(f, "halving"),
+        }
+    }
+}
+
+// The ranges that candidates are drawn from.
+#[derive(Clone, Debug)]
+pub struct Space {
+    pub populations: (usize, usize),
+    pub mutation_rates: (f32, f32),
+    // Tournaments run from 2 up to this size; worst-best selection is
+    // tried as often as all tournament sizes together.
+    pub max_tournament: usize,
+    pub crossovers: Vec<Crossover>,
+}
+
+impl Default for Space {
+    fn default() -> Space {
+        Space {
+            populations: (4, 64),
+            mutation_rates: (0.0, 1.0),
+            max_tournament: 5,
+            crossovers: vec![Crossover::OnePoint, Crossover::Uniform],
+        }
+    }
+}
+
+impl Space {
+    // Draw a random candidate for `environments`.
+    pub fn sample<R: Rng>(
+        &self,
+        environments: &[String],
+        max_generations: usize,
+        rng: &mut R,
+    ) -> Config {
+        let selection = if self.max_tournament < 2 || rng.gen() {
+            Selection::WorstBest
+        } else {
+            Selection::Tournament(rng.gen_range(2, self.max_tournament + 1))
+        };
+        let (low, high) = self.mutation_rates;
+        // Round to a hundredth, to keep the output readable.
+        let mutation_rate = ((low + rng.gen::<f32>() * (high - low)) * 100.0).round() / 100.0;
+        Config {
+            environments: Some(environments.to_vec()),
+            population: Some(rng.gen_range(self.populations.0, self.populations.1 + 1)),
+            operators: OperatorsConfig {
+                selection: Some(selection),
+                crossover: Some(self.crossovers[rng.gen_range(0, self.crossovers.len())]),
+                mutation_rate: Some(mutation_rate),
+            },
+            stop: StopConfig {
+                target_score: None,
+                max_generations: Some(max_generations),
+            },
+            ..Config::default()
+        }
+    }
+}
+
+// A candidate and the runs it has had so far.
+#[derive(Clone, Debug)]
+pub struct Trial {
+    pub config: Config,
+    pub runs: usize,
+    pub solved: usize,
+    pub total_generations: usize,
+}
+
+impl Trial {
+    fn new(config: Config) -> Trial {
+        Trial {
+            config,
+            runs: 0,
+            solved: 0,
+            total_generations: 0,
+        }
+    }
+
+    // Mean generations per run; lower is better.
+    pub fn cost(&self) -> f64 {
+        (self.total_generations as f64) / (self.runs as f64)
+    }
+}
+
+// Run `config` on each of its environments with every seed, and return
+// (solved, generations) for each run.
+pub fn evaluate(config: &Config, seeds: &[u64]) -> Vec<(bool, usize)> {
+    let params = config.params();
+    let jobs: Vec<(usize, u64)> = (0..params.len())
+        .flat_map(|i| seeds.iter().map(move |&seed| (i, seed)))
+        .collect();
+    jobs.par_iter()
+        .map(|&(i, seed)| run_once(&params[i], seed))
+        .collect()
+}
+
+// Run `trial` with `seeds` and add the results to its record.
+fn extend<E>(trial: &mut Trial, seeds: &[u64], evaluate: &E)
+where
+    E: Fn(&Config, &[u64]) -> Vec<(bool, usize)>,
+{
+    for (solved, generations) in evaluate(&trial.config, seeds) {
+        trial.runs += 1;
+        trial.solved += solved as usize;
+        trial.total_generations += generations;
+    }
+}
+
+fn by_cost(trials: &mut [Trial]) {
+    trials.sort_by(|a, b| a.cost().partial_cmp(&b.cost()).unwrap());
+}
+
+// Search `candidates` and return them, best first.  Random search gives
+// every candidate `seeds` seeds; successive halving starts with `seeds`
+// and doubles them each round.  `evaluate` runs a candidate with the
+// given seeds.
+pub fn search<E>(method: Method, candidates: Vec<Config>, seeds: u64, evaluate: E) -> Vec<Trial>
+where
+    E: Fn(&Config, &[u64]) -> Vec<(bool, usize)> + Sync,
+{
+    let mut trials: Vec<Trial> = candidates.into_iter().map(Trial::new).collect();
+    match method {
+        Method::Random => {
+            let seeds: Vec<u64> = (0..seeds).collect();
+            trials
+                .par_iter_mut()
+                .for_each(|trial| extend(trial, &seeds, &evaluate));
+            by_cost(&mut trials);
+            trials
+        }
+        Method::Halving => {
+            // The candidates dropped in each round, best first.
+            let mut dropped = vec![];
+            let mut used = 0;
+            let mut batch = seeds;
+            loop {
+                let seeds: Vec<u64> = (used..used + batch).collect();
+                trials
+                    .par_iter_mut()
+                    .for_each(|trial| extend(trial, &seeds, &evaluate));
+                by_cost(&mut trials);
+                if trials.len() <= 1 {
+                    break;
+                }
+                dropped.push(trials.split_off(trials.len().div_ceil(2)));
+                used += batch;
+                batch = used;
+            }
+            // Candidates that lasted longer rank higher.
+            for round in dropped.into_iter().rev() {
+                trials.extend(round);
+            }
+            trials
+        }
+    }
+}
+
+// Draw `candidates` random settings and search them.
+pub fn tune<R: Rng>(
+    method: Method,
+    space: &Space,
+    environments: &[String],
+    candidates: usize,
+    seeds: u64,
+    max_generations: usize,
+    rng: &mut R,
+) -> Vec<Trial> {
+    let configs = (0..candidates)
+        .map(|_| space.sample(environments, max_generations, rng))
+        .collect();
+    search(method, configs, seeds, evaluate)
+}
+
+// The best trial's config as TOML, with comments describing how it did.
+pub fn report(trials: &[Trial]) -> String {
+    let best = &trials[0];
+    let mut text = format!(
+        "# Tuned over {} candidates; mean {:.1} generations over {} runs, {} solved.\n",
+        trials.len(),
+        best.cost(),
+        best.runs,
+        best.solved
+    );
+    text.push_str(&toml::to_string(&best.config).expect("Failed to serialize config"));
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    fn candidate(population: usize) -> Config {
+        Config {
+            population: Some(population),
+            ..Config::default()
+        }
+    }
+
+    // Pretend that every run takes as many generations as the
+    // population size.
+    fn fake_evaluate(config: &Config, seeds: &[u64]) -> Vec<(bool, usize)> {
+        vec![(true, config.population.unwrap()); seeds.len()]
+    }
+
+    #[test]
+    fn test_random_search() {
+        let candidates = vec![candidate(30), candidate(10), candidate(20)];
+        let trials = search(Method::Random, candidates, 4, fake_evaluate);
+        let populations: Vec<usize> = trials
+            .iter()
+            .map(|t| t.config.population.unwrap())
+            .collect();
+        assert_eq!(populations, [10, 20, 30]);
+        assert!(trials.iter().all(|t| t.runs == 4));
+        assert_eq!(trials[0].cost(), 10.0);
+    }
+
+    #[test]
+    fn test_successive_halving() {
+        let candidates = (1..=8).rev().map(candidate).collect();
+        let trials = search(Method::Halving, candidates, 2, fake_evaluate);
+        let populations: Vec<usize> = trials
+            .iter()
+            .map(|t| t.config.population.unwrap())
+            .collect();
+        // The best candidate survives all four rounds: 2 + 2 + 4 + 8 runs.
+        assert_eq!(populations, [1, 2, 3, 4, 5, 6, 7, 8]);
+        let runs: Vec<usize> = trials.iter().map(|t| t.runs).collect();
+        assert_eq!(runs, [16, 8, 4, 4, 2, 2, 2, 2]);
+    }
+
+    #[test]
+    fn test_tuned_config_is_usable() {
+        let mut rng = StdRng::from_seed(&[41][..]);
+        let environments = vec![String::from("0011")];
+        let trials = tune(
+            Method::Halving,
+            &Space::default(),
+            &environments,
+            4,
+            2,
+            500,
+            &mut rng,
+        );
+        assert_eq!(trials.len(), 4);
+        let text = report(&trials);
+        assert!(text.starts_with("# Tuned over 4 candidates"));
+        let config: Config = toml::from_str(&text).unwrap();
+        config.validate().unwrap();
+        assert_eq!(config, trials[0].config);
+        assert_eq!(config.environments, Some(environments));
+        assert_eq!(config.stop.max_generations, Some(500));
+    }
+}
@@ -0,0 +1,633 @@
+// Alternative genome kinds, for comparing how quickly each one learns.
+//
+// Flib is a Mealy machine: each transition carries an output.  This module
+// adds two more representations, each with its own chromosome encoding,
+// mutation operator and crossover:
+//
+// * A Moore machine attaches the output to the state instead.  Each state
+//   is three characters, its output followed by the destinations on inputs
+//   0 and 1, so "0BA1AB" is a two-state machine.  After each input symbol
+//   the machine moves to the new state and predicts that state's output.
+//
+// * A probabilistic flib gives each transition a probability of
+//   outputting a 1, written as a digit from 0 (never) to 9 (always), in
+//   place of Flib's output character; "9B0A3A7B" is a two-state machine.
+//   Its state transitions are still deterministic, so its fitness can be
+//   computed exactly, either as the expected accuracy or from the
+//   log-likelihood of the environment.
+//
+// Crossover on these kinds only cuts between genes, so a child never gets
+// a Moore state's output from one parent and its transitions from the
+// other, or a probability from one parent and its destination from the
+// other.  Mealy chromosomes keep the character-level crossover that
+// simulate() uses.
+
+use std::fmt;
+use std::str::FromStr;
+
+use rand::Rng;
+use rayon::prelude::*;
+
+use crate::cache::CacheStats;
+use crate::experiment::{summarize, RunResult, Summary};
+use crate::{
+    char_to_state, find_minmax, find_score, make_from_chromosome, mutate, seeded_rng,
+    state_to_char, tournament, Crossover, Outcome, Selection, SimParams,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct MooreFlib {
+    // Each state's output, and its destinations on inputs 0 and 1.
+    states: Vec<(char, [usize; 2])>,
+}
+
+impl MooreFlib {
+    pub fn from_chromosome(chromosome: &str) -> MooreFlib {
+        let chars: Vec<char> = chromosome.chars().collect();
+        assert!(
+            chars.len().is_multiple_of(3),
+            "Moore chromosome length must be a multiple of 3"
+        );
+        let states = chars
+            .chunks(3)
+            .map(|gene| (gene[0], [char_to_state(gene[1]), char_to_state(gene[2])]))
+            .collect();
+        MooreFlib { states }
+    }
+
+    pub fn as_chromosome(&self) -> String {
+        let mut c = String::new();
+        for &(output, dests) in &self.states {
+            c.push(output);
+            c.push(state_to_char(dests[0]));
+            c.push(state_to_char(dests[1]));
+        }
+        c
+    }
+
+    pub fn random_chromosome<R: Rng>(num_states: usize, rng: &mut R) -> String {
+        let mut c = String::new();
+        for _ in 0..num_states {
+            c.push((b'0' + rng.gen_range(0, 2)) as char);
+            c.push(state_to_char(rng.gen_range(0, num_states)));
+            c.push(state_to_char(rng.gen_range(0, num_states)));
+        }
+        c
+    }
+
+    // Same scoring as Flib::predict(): run over two copies of the
+    // environment, predicting each following symbol.
+    pub fn predict(&self, environment: &str) -> f32 {
+        let mut state = 0;
+        let mut matches = 0;
+        for (input, expected) in doubled_steps(environment) {
+            state = self.states[state].1[input];
+            if self.states[state].0 == expected {
+                matches += 1;
+            }
+        }
+        (matches as f32) / ((environment.len() * 2) as f32)
+    }
+}
+
+// Flip an output or pick a new destination state, which, as in
+// mutate_at_index(), may be the one it already had.
+pub fn mutate_moore<R: Rng>(chromosome: &str, rng: &mut R) -> String {
+    let num_states = chromosome.len() / 3;
+    let position = rng.gen_range(0, chromosome.len());
+    chromosome
+        .chars()
+        .enumerate()
+        .map(|(i, ch)| match (i == position, i % 3) {
+            (false, _) => ch,
+            (true, 0) => flip(ch),
+            (true, _) => state_to_char(rng.gen_range(0, num_states)),
+        })
+        .collect()
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ProbabilisticFlib {
+    // For each state and input, the probability of outputting a 1 in
+    // ninths, and the destination state.
+    states: Vec<[(u32, usize); 2]>,
+}
+
+// How a probabilistic flib's predictions are turned into a fitness
+// between 0.0 and 1.0.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ProbFitness {
+    // Expected fraction of correct predictions.
+    Expected,
+    // Geometric mean of the probability given to each actual symbol,
+    // which is exp() of the mean log-likelihood per symbol.
+    LogLikelihood,
+}
+
+impl ProbabilisticFlib {
+    pub fn from_chromosome(chromosome: &str) -> ProbabilisticFlib {
+        let chars: Vec<char> = chromosome.chars().collect();
+        assert!(
+            chars.len().is_multiple_of(4),
+            "probabilistic chromosome length must be a multiple of 4"
+        );
+        let states = chars
+            .chunks(4)
+            .map(|gene| {
+                [
+                    (digit(gene[0]), char_to_state(gene[1])),
+                    (digit(gene[2]), char_to_state(gene[3])),
+                ]
+            })
+            .collect();
+        ProbabilisticFlib { states }
+    }
+
+    pub fn as_chromosome(&self) -> String {
+        let mut c = String::new();
+        for transitions in &self.states {
+            for &(ninths, dest) in transitions {
+                c.push(std::char::from_digit(ninths, 10).unwrap());
+                c.push(state_to_char(dest));
+            }
+        }
+        c
+    }
+
+    pub fn random_chromosome<R: Rng>(num_states: usize, rng: &mut R) -> String {
+        let mut c = String::new();
+        for _ in 0..num_states * 2 {
+            c.push(std::char::from_digit(rng.gen_range(0, 10), 10).unwrap());
+            c.push(state_to_char(rng.gen_range(0, num_states)));
+        }
+        c
+    }
+
+    pub fn predict(&self, environment: &str, fitness: ProbFitness) -> f32 {
+        let mut state = 0;
+        let mut total = 0.0;
+        for (input, expected) in doubled_steps(environment) {
+            let (ninths, dest) = self.states[state][input];
+            let p_one = f64::from(ninths) / 9.0;
+            let p = if expected == '1' { p_one } else { 1.0 - p_one };
+            total += match fitness {
+                ProbFitness::Expected => p,
+                ProbFitness::LogLikelihood => p.ln(),
+            };
+            state = dest;
+        }
+        let mean = total / ((environment.len() * 2) as f64);
+        match fitness {
+            ProbFitness::Expected => mean as f32,
+            ProbFitness::LogLikelihood => mean.exp() as f32,
+        }
+    }
+}
+
+// Nudge a probability up or down by a ninth, or pick a new destination
+// state.  Small steps suit probabilities better than the random
+// replacement used for states.
+pub fn mutate_probabilistic<R: Rng>(chromosome: &str, rng: &mut R) -> String {
+    let num_states = chromosome.len() / 4;
+    let position = rng.gen_range(0, chromosome.len());
+    chromosome
+        .chars()
+        .enumerate()
+        .map(|(i, ch)| match (i == position, i % 2) {
+            (false, _) => ch,
+            (true, 0) => {
+                let ninths = match digit(ch) {
+                    0 => 1,
+                    9 => 8,
+                    d if rng.gen() => d + 1,
+                    d => d - 1,
+                };
+                std::char::from_digit(ninths, 10).unwrap()
+            }
+            (true, _) => state_to_char(rng.gen_range(0, num_states)),
+        })
+        .collect()
+}
+
+// Cross two chromosomes made of `gene_len`-character genes, taking each
+// gene whole from one parent or the other: the genes before a random one
+// from `parent1` for a one-point crossover, or each at random for a
+// uniform one.
+fn combine_genes<R: Rng>(
+    crossover: Crossover,
+    parent1: &str,
+    parent2: &str,
+    gene_len: usize,
+    rng: &mut R,
+) -> String {
+    let genes1 = parent1.as_bytes().chunks(gene_len);
+    let genes2 = parent2.as_bytes().chunks(gene_len);
+    let split = match crossover {
+        Crossover::OnePoint => rng.gen_range(0, genes1.len()),
+        Crossover::Uniform => 0,
+    };
+    let mut child = String::new();
+    for (i, (gene1, gene2)) in genes1.zip(genes2).enumerate() {
+        let from_first = match crossover {
+            Crossover::OnePoint => i < split,
+            Crossover::Uniform => rng.gen(),
+        };
+        let gene = if from_first { gene1 } else { gene2 };
+        child.extend(gene.iter().map(|&b| b as char));
+    }
+    child
+}
+
+fn digit(ch: char) -> u32 {
+    ch.to_digit(10).expect("probability must be a digit")
+}
+
+fn flip(ch: char) -> char {
+    match ch {
+        '0' => '1',
+        '1' => '0',
+        _ => panic!("unexpected character in chromosome"),
+    }
+}
+
+// The (input, expected output) pairs that predict() steps through: two
+// copies of the environment, each symbol paired with the one after it.
+fn doubled_steps(environment: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let inputs = environment.chars().chain(environment.chars());
+    let expected = inputs.clone().skip(1).chain(environment.chars().take(1));
+    inputs
+        .map(|ch| (ch as usize) - ('0' as usize))
+        .zip(expected)
+}
+
+// Which representation a run evolves.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Kind {
+    Mealy,
+    Moore,
+    Probabilistic(ProbFitness),
+}
+
+impl Kind {
+    pub fn random_chromosome<R: Rng>(self, num_states: usize, rng: &mut R) -> String {
+        match self {
+            Kind::Mealy => {
+                let mut flib = make_from_chromosome(String::new());
+                flib.randomize(num_states, rng);
+                flib.as_chromosome()
+            }
+            Kind::Moore => MooreFlib::random_chromosome(num_states, rng),
+            Kind::Probabilistic(_) => ProbabilisticFlib::random_chromosome(num_states, rng),
+        }
+    }
+
+    pub fn fitness(self, chromosome: &str, environment: &str) -> f32 {
+        match self {
+            Kind::Mealy => make_from_chromosome(chromosome.to_string()).predict(environment),
+            Kind::Moore => MooreFlib::from_chromosome(chromosome).predict(environment),
+            Kind::Probabilistic(fitness) => {
+                ProbabilisticFlib::from_chromosome(chromosome).predict(environment, fitness)
+            }
+        }
+    }
+
+    pub fn mutate<R: Rng>(self, chromosome: &str, rng: &mut R) -> String {
+        match self {
+            Kind::Mealy => mutate(chromosome, rng),
+            Kind::Moore => mutate_moore(chromosome, rng),
+            Kind::Probabilistic(_) => mutate_probabilistic(chromosome, rng),
+        }
+    }
+
+    // Cross two parents with `crossover`, cutting only between whole
+    // states for Moore machines and whole transitions for probabilistic
+    // flibs.
+    pub fn combine<R: Rng>(
+        self,
+        crossover: Crossover,
+        parent1: &str,
+        parent2: &str,
+        rng: &mut R,
+    ) -> String {
+        match self {
+            Kind::Mealy => crossover.combine(parent1, parent2, rng),
+            Kind::Moore => combine_genes(crossover, parent1, parent2, 3, rng),
+            Kind::Probabilistic(_) => combine_genes(crossover, parent1, parent2, 2, rng),
+        }
+    }
+}
+
+impl FromStr for Kind {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Kind, String> {
+        match s {
+            "mealy" => Ok(Kind::Mealy),
+            "moore" => Ok(Kind::Moore),
+            "probabilistic:expected" => Ok(Kind::Probabilistic(ProbFitness::Expected)),
+            "probabilistic:log-likelihood" => Ok(Kind::Probabilistic(ProbFitness::LogLikelihood)),
+            _ => Err(format!(
+                "unknown genome kind '{}'; expected mealy, moore, probabilistic:expected \
+                 or probabilistic:log-likelihood",
+                s
+            )),
+        }
+    }
+}
+
+impl fmt::Display for Kind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Kind::Mealy => write!(f, "mealy"),
+            Kind::Moore => write!(f, "moore"),
+            Kind::Probabilistic(ProbFitness::Expected) => write!(f, "probabilistic:expected"),
+            Kind::Probabilistic(ProbFitness::LogLikelihood) => {
+                write!(f, "probabilistic:log-likelihood")
+            }
+        }
+    }
+}
+
+// Evolve chromosomes of the given kind with the same steady-state scheme
+// as simulate(): each generation the child of two selected parents
+// replaces the worst-scoring individual, and then one random individual
+// may be mutated.
+pub fn evolve<R: Rng>(kind: Kind, params: &SimParams, rng: &mut R) -> Outcome {
+    let environment = &params.environment;
+    evolve_with(kind, params, |c| kind.fitness(c, environment), rng)
+}
+
+// Like evolve(), but scoring chromosomes with `fitness` rather than by
+// predicting params.environment.  This always uses steady-state
+// replacement, whatever params.replacement says.
+pub fn evolve_with<R: Rng, F: Fn(&str) -> f32>(
+    kind: Kind,
+    params: &SimParams,
+    fitness: F,
+    rng: &mut R,
+) -> Outcome {
+    let operators = &params.operators;
+    let mut population: Vec<String> = (0..params.population_size)
+        .map(|_| kind.random_chromosome(params.num_states, rng))
+        .collect();
+    let mut scores: Vec<f32> = population.iter().map(|c| fitness(c)).collect();
+
+    let mut generation = 0;
+    loop {
+        if let Some(v) = find_score(&scores, params.target_score) {
+            return Outcome {
+                generation,
+                solution: Some(population[v].clone()),
+                best: population[v].clone(),
+                best_score: scores[v],
+                best_id: None,
+                cache: CacheStats::default(),
+            };
+        }
+        if Some(generation) == params.max_generations {
+            let (_, max_index) = find_minmax(&scores);
+            return Outcome {
+                generation,
+                solution: None,
+                best: population[max_index].clone(),
+                best_score: scores[max_index],
+                best_id: None,
+                cache: CacheStats::default(),
+            };
+        }
+
+        let (min_index, max_index) = find_minmax(&scores);
+        let (parent1, parent2) = match operators.selection {
+            Selection::WorstBest => (min_index, max_index),
+            Selection::Tournament(size) => (
+                tournament(&scores, size, rng),
+                tournament(&scores, size, rng),
+            ),
+        };
+        let embryo = kind.combine(
+            operators.crossover,
+            &population[parent1],
+            &population[parent2],
+            rng,
+        );
+        scores[min_index] = fitness(&embryo);
+        population[min_index] = embryo;
+
+        let random_index = rng.gen_range(0, population.len());
+        if random_index != max_index
+            && random_index != min_index
+            && rng.gen::<f32>() < operators.mutation_rate
+        {
+            let mutant = kind.mutate(&population[random_index], rng);
+            scores[random_index] = fitness(&mutant);
+            population[random_index] = mutant;
+        }
+
+        generation += 1;
+    }
+}
+
+// Run every kind on every environment with each seed, in parallel, and
+// summarize each (kind, environment) pair.  Machines get `states` states,
+// or by default half the environment's length plus one; other settings
+// come from `base`.
+pub fn compare(
+    kinds: &[Kind],
+    environments: &[String],
+    states: Option<usize>,
+    base: &SimParams,
+    seeds: &[u64],
+) -> Vec<(Kind, String, Summary)> {
+    let settings: Vec<(Kind, SimParams)> = kinds
+        .iter()
+        .flat_map(|&kind| {
+            environments.iter().map(move |environment| {
+                let params = SimParams {
+                    environment: environment.clone(),
+                    num_states: states.unwrap_or(environment.len() / 2 + 1),
+                    ..base.clone()
+                };
+                (kind, params)
+            })
+        })
+        .collect();
+    let jobs: Vec<(usize, u64)> = (0..settings.len())
+        .flat_map(|setting| seeds.iter().map(move |&seed| (setting, seed)))
+        .collect();
+    let results: Vec<RunResult> = jobs
+        .par_iter()
+        .map(|&(setting, seed)| {
+            let (kind, params) = &settings[setting];
+            let mut rng = seeded_rng(seed);
+            let outcome = evolve(*kind, params, &mut rng);
+            RunResult {
+                setting,
+                seed,
+                solved: outcome.solution.is_some(),
+                generations: outcome.generation,
+            }
+        })
+        .collect();
+
+    settings
+        .into_iter()
+        .enumerate()
+        .map(|(i, (kind, params))| {
+            let runs: Vec<RunResult> = results.iter().filter(|r| r.setting == i).cloned().collect();
+            (kind, params.environment, summarize(&runs))
+        })
+        .collect()
+}
+
+pub fn comparison_table(rows: &[(Kind, String, Summary)]) -> String {
+    let mut table = format!(
+        "{:<28}  {:<12}  {:>11}  {:>8}  {:>8}\n",
+        "kind", "environment", "success", "mean gen", "median"
+    );
+    for (kind, environment, s) in rows {
+        table.push_str(&format!(
+            "{:<28}  {:<12}  {:>5}/{:<5}  {:>8.1}  {:>8.1}\n",
+            kind.to_string(),
+            environment,
+            s.successes,
+            s.runs,
+            s.mean_generations,
+            s.median_generations
+        ));
+    }
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{SeedableRng, StdRng};
+
+    #[test]
+    fn test_moore_round_trip() {
+        let flib = MooreFlib::from_chromosome("0BA1AB");
+        assert_eq!(flib.states, vec![('0', [1, 0]), ('1', [0, 1])]);
+        assert_eq!(flib.as_chromosome(), "0BA1AB");
+    }
+
+    #[test]
+    fn test_moore_predict() {
+        // State A predicts 0 and state B predicts 1, and each input moves
+        // to the matching state: an echo, which is right on "0011" half
+        // the time.
+        let flib = MooreFlib::from_chromosome("0AB1AB");
+        assert_eq!(flib.predict("0011"), 0.5);
+        // Alternating between the two states predicts "01" perfectly.
+        let flib = MooreFlib::from_chromosome("0BB1AA");
+        assert_eq!(flib.predict("01"), 1.0);
+    }
+
+    #[test]
+    fn test_probabilistic_predict() {
+        // A certain echo matches a Mealy flib's score exactly.
+        let echo = ProbabilisticFlib::from_chromosome("0A9A");
+        let mut mealy = make_from_chromosome(String::from("0A1A"));
+        assert_eq!(
+            echo.predict("011001", ProbFitness::Expected),
+            mealy.predict("011001")
+        );
+        assert_eq!(echo.as_chromosome(), "0A9A");
+
+        // A coin flip is right half the time whatever the environment.
+        let coin = ProbabilisticFlib::from_chromosome("4A4A");
+        let p: f64 = 4.0 / 9.0;
+        let expected = coin.predict("0011", ProbFitness::Expected);
+        assert!((expected - 0.5).abs() < 1e-6);
+        let likelihood = coin.predict("0011", ProbFitness::LogLikelihood);
+        assert!((f64::from(likelihood) - (p * (1.0 - p)).sqrt()).abs() < 1e-6);
+        // A certain wrong prediction has zero likelihood.
+        assert_eq!(echo.predict("0011", ProbFitness::LogLikelihood), 0.0);
+    }
+
+    #[test]
+    fn test_mutations_keep_encoding() {
+        let mut rng = StdRng::from_seed(&[33][..]);
+        for _ in 0..100 {
+            let moore = mutate_moore("0BA1AB", &mut rng);
+            assert_eq!(MooreFlib::from_chromosome(&moore).as_chromosome(), moore);
+            let prob = mutate_probabilistic("9B0A3A7B", &mut rng);
+            let changed: Vec<_> = prob
+                .chars()
+                .zip("9B0A3A7B".chars())
+                .filter(|(a, b)| a != b)
+                .collect();
+            assert!(changed.len() <= 1);
+            if let Some(&(new, old)) = changed.first() {
+                if old.is_ascii_digit() {
+                    assert_eq!((digit(new) as i32 - digit(old) as i32).abs(), 1);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_crossover_keeps_genes() {
+        let mut rng = StdRng::from_seed(&[34][..]);
+        let cases = [
+            (Kind::Moore, "0AA1BB0CC", "1BC0CA1AB", 3),
+            (
+                Kind::Probabilistic(ProbFitness::Expected),
+                "9A0B",
+                "3B7A",
+                2,
+            ),
+        ];
+        for &(kind, parent1, parent2, gene_len) in &cases {
+            for &crossover in &[Crossover::OnePoint, Crossover::Uniform] {
+                for _ in 0..50 {
+                    let child = kind.combine(crossover, parent1, parent2, &mut rng);
+                    assert_eq!(child.len(), parent1.len());
+                    for start in (0..child.len()).step_by(gene_len) {
+                        let gene = &child[start..start + gene_len];
+                        assert!(
+                            gene == &parent1[start..start + gene_len]
+                                || gene == &parent2[start..start + gene_len],
+                            "{} split a gene in {}",
+                            kind,
+                            child
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_kind_names() {
+        for name in &[
+            "mealy",
+            "moore",
+            "probabilistic:expected",
+            "probabilistic:log-likelihood",
+        ] {
+            assert_eq!(name.parse::<Kind>().unwrap().to_string(), *name);
+        }
+        assert!("mealy-ish".parse::<Kind>().is_err());
+    }
+
+    #[test]
+    fn test_evolve_solves_each_kind() {
+        for &kind in &[
+            Kind::Mealy,
+            Kind::Moore,
+            Kind::Probabilistic(ProbFitness::Expected),
+            Kind::Probabilistic(ProbFitness::LogLikelihood),
+        ] {
+            let params = SimParams {
+                environment: String::from("01"),
+                num_states: 2,
+                max_generations: Some(5000),
+                ..SimParams::default()
+            };
+            let mut rng = StdRng::from_seed(&[3][..]);
+            let outcome = evolve(kind, &params, &mut rng);
+            let solution = outcome.solution.expect("no solution found");
+            assert_eq!(kind.fitness(&solution, "01"), 1.0, "{}", kind);
+        }
+    }
+}
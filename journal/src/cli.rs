@@ -9,25 +9,57 @@ pub enum Action {
     #[structopt()]
     text: String,
   },
-  /// Remove an entry from the journal file
+  /// Mark a task as finished
   Done {
     #[structopt()]
     position: usize,
   },
-  /// List all tasks
-  List,
+  /// Mark a finished task as not done, by its position in `list --done`
+  Undo {
+    #[structopt()]
+    position: usize,
+  },
+  /// List all open tasks
+  List {
+    /// List finished tasks instead
+    #[structopt(long)]
+    done: bool,
+  },
+  /// Remove finished tasks from the journal file
+  Purge {
+    /// Only remove tasks finished at least this many days ago
+    #[structopt(long, default_value = "30")]
+    older_than: u32,
+  },
 }
 
 #[derive(Debug, StructOpt)]
 #[structopt(
     name = "Journal",
     about = "A command-line tool written in Rust",
+    after_help = "EXIT STATUS:
+    0    success
+    1    invalid command line
+    3    no journal file yet
+    4    permission denied for the journal file
+    5    the journal file is corrupt
+    6    no task at that position
+    7    another journal command held the journal too long
+    8    bad config file, or nowhere to keep the journal
+    9    any other I/O error",
 )]
 pub struct CommandLineArgs {
     #[structopt(subcommand)]
     pub action: Action,
 
-    /// Use a different journal file.
+    /// Use a different journal file.  Defaults to $JOURNAL_FILE, then the
+    /// `journal_file` setting in $XDG_CONFIG_HOME/journal/config.json, then
+    /// $XDG_DATA_HOME/journal/journal.json.
     #[structopt(parse(from_os_str), short, long)]
     pub journal_file: Option<PathBuf>,
+
+    /// Seconds to wait while another journal command is using the
+    /// journal file.  Defaults to 10.
+    #[structopt(long)]
+    pub lock_timeout: Option<f64>,
 }
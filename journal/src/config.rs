@@ -0,0 +1,202 @@
+use std::env;
+use std::ffi::OsString;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use serde::Deserialize;
+
+use crate::error::JournalError;
+use crate::lock::DEFAULT_TIMEOUT;
+
+// Looks up an environment variable, so tests can supply their own.
+type Env<'a> = dyn Fn(&str) -> Option<OsString> + 'a;
+
+fn process_env(name: &str) -> Option<OsString> {
+    env::var_os(name)
+}
+
+/// Settings read from `$XDG_CONFIG_HOME/journal/config.json`, for example:
+///
+/// ```json
+/// { "journal_file": "/home/me/notes/journal.json", "lock_timeout": 30 }
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+    pub journal_file: Option<PathBuf>,
+    /// Seconds to wait for another journal command to finish.
+    pub lock_timeout: Option<f64>,
+}
+
+impl Config {
+    /// Load the config file, if there is one.
+    pub fn load() -> Result<Config, JournalError> {
+        let path = match config_file(&process_env) {
+            Some(path) if path.exists() => path,
+            _ => return Ok(Config::default()),
+        };
+        let text = fs::read_to_string(&path).map_err(|e| {
+            JournalError::Config(format!("can't read config file {}: {}", path.display(), e))
+        })?;
+        serde_json::from_str(&text).map_err(|e| {
+            JournalError::Config(format!("invalid config file {}: {}", path.display(), e))
+        })
+    }
+}
+
+/// Find the journal file: the `--journal-file` flag, then the
+/// `JOURNAL_FILE` environment variable, then the config file, and finally
+/// `journal.json` in the per-user data directory.
+pub fn journal_path(flag: Option<PathBuf>, config: &Config) -> Result<PathBuf, JournalError> {
+    find_journal(flag, config, &process_env)
+}
+
+fn find_journal(
+    flag: Option<PathBuf>,
+    config: &Config,
+    env: &Env,
+) -> Result<PathBuf, JournalError> {
+    if let Some(path) = flag {
+        return Ok(path);
+    }
+    if let Some(path) = env_path(env, "JOURNAL_FILE") {
+        return Ok(path);
+    }
+    if let Some(path) = &config.journal_file {
+        return Ok(path.clone());
+    }
+    data_dir(env)
+        .map(|dir| dir.join("journal").join("journal.json"))
+        .ok_or_else(|| {
+            JournalError::Config(String::from(
+                "can't find a home directory; use --journal-file or set JOURNAL_FILE",
+            ))
+        })
+}
+
+/// How long to wait for a lock on the journal: the `--lock-timeout` flag,
+/// then the config file, then the default.
+pub fn lock_timeout(flag: Option<f64>, config: &Config) -> Result<Duration, JournalError> {
+    match flag.or(config.lock_timeout) {
+        Some(seconds) => Duration::try_from_secs_f64(seconds).map_err(|_| {
+            JournalError::Config(format!(
+                "lock timeout {} must be a non-negative number of seconds",
+                seconds
+            ))
+        }),
+        None => Ok(DEFAULT_TIMEOUT),
+    }
+}
+
+/// Create the directory that will hold `path`, if it doesn't exist yet.
+pub fn create_parent_dirs(path: &Path) -> std::io::Result<()> {
+    match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => fs::create_dir_all(parent),
+        _ => Ok(()),
+    }
+}
+
+// An environment variable holding a path; unset and empty are the same.
+fn env_path(env: &Env, name: &str) -> Option<PathBuf> {
+    env(name)
+        .filter(|value| !value.is_empty())
+        .map(PathBuf::from)
+}
+
+fn config_file(env: &Env) -> Option<PathBuf> {
+    env_path(env, "XDG_CONFIG_HOME")
+        .or_else(|| env_path(env, "HOME").map(|home| home.join(".config")))
+        .map(|dir| dir.join("journal").join("config.json"))
+}
+
+fn data_dir(env: &Env) -> Option<PathBuf> {
+    env_path(env, "XDG_DATA_HOME")
+        .or_else(|| env_path(env, "HOME").map(|home| home.join(".local/share")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // An environment holding just `vars`.
+    fn fake_env(
+        vars: &'static [(&'static str, &'static str)],
+    ) -> impl Fn(&str) -> Option<OsString> {
+        move |name| {
+            vars.iter()
+                .find(|(var, _)| *var == name)
+                .map(|(_, value)| OsString::from(value))
+        }
+    }
+
+    fn config(journal_file: Option<&str>) -> Config {
+        Config {
+            journal_file: journal_file.map(PathBuf::from),
+            lock_timeout: None,
+        }
+    }
+
+    #[test]
+    fn test_journal_path_order() {
+        let everything = fake_env(&[
+            ("JOURNAL_FILE", "/env/journal.json"),
+            ("XDG_DATA_HOME", "/data"),
+            ("HOME", "/home/me"),
+        ]);
+        let configured = config(Some("/config/journal.json"));
+        let flag = Some(PathBuf::from("/flag/journal.json"));
+        let find = |flag, config: &Config, env: &Env| find_journal(flag, config, env).unwrap();
+
+        assert_eq!(
+            find(flag, &configured, &everything),
+            PathBuf::from("/flag/journal.json")
+        );
+        assert_eq!(
+            find(None, &configured, &everything),
+            PathBuf::from("/env/journal.json")
+        );
+        let no_variable = fake_env(&[("XDG_DATA_HOME", "/data"), ("HOME", "/home/me")]);
+        assert_eq!(
+            find(None, &configured, &no_variable),
+            PathBuf::from("/config/journal.json")
+        );
+        assert_eq!(
+            find(None, &config(None), &no_variable),
+            PathBuf::from("/data/journal/journal.json")
+        );
+        let home_only = fake_env(&[("HOME", "/home/me")]);
+        assert_eq!(
+            find(None, &config(None), &home_only),
+            PathBuf::from("/home/me/.local/share/journal/journal.json")
+        );
+        match find_journal(None, &config(None), &fake_env(&[])) {
+            Err(JournalError::Config(_)) => {}
+            other => panic!("expected a config error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_empty_variables_are_unset() {
+        let empty = fake_env(&[
+            ("JOURNAL_FILE", ""),
+            ("XDG_DATA_HOME", ""),
+            ("XDG_CONFIG_HOME", ""),
+            ("HOME", "/home/me"),
+        ]);
+        assert_eq!(
+            find_journal(None, &config(None), &empty).unwrap(),
+            PathBuf::from("/home/me/.local/share/journal/journal.json")
+        );
+        assert_eq!(
+            config_file(&empty),
+            Some(PathBuf::from("/home/me/.config/journal/config.json"))
+        );
+        let xdg = fake_env(&[("XDG_CONFIG_HOME", "/config"), ("HOME", "/home/me")]);
+        assert_eq!(
+            config_file(&xdg),
+            Some(PathBuf::from("/config/journal/config.json"))
+        );
+        assert_eq!(config_file(&fake_env(&[("HOME", "")])), None);
+    }
+}
@@ -0,0 +1,191 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Everything that can go wrong with a journal command.
+#[derive(Debug)]
+pub enum JournalError {
+    /// The journal file doesn't exist.
+    NotFound(PathBuf),
+    /// The journal file or its directory can't be read or written.
+    PermissionDenied(PathBuf),
+    /// The journal file isn't a valid list of tasks.
+    Corrupt {
+        path: PathBuf,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// A task position that isn't in the list.
+    InvalidTaskId { position: usize, count: usize },
+    /// Another journal command held the journal file for longer than
+    /// `timeout`.
+    LockContention { path: PathBuf, timeout: Duration },
+    /// The config file couldn't be read or the journal file located.
+    Config(String),
+    /// Any other I/O failure.
+    Io { path: PathBuf, source: io::Error },
+}
+
+impl JournalError {
+    /// Classify an I/O error on the journal at `path`.
+    pub fn io(path: &Path, source: io::Error) -> JournalError {
+        let path = path.to_path_buf();
+        match source.kind() {
+            io::ErrorKind::NotFound => JournalError::NotFound(path),
+            io::ErrorKind::PermissionDenied => JournalError::PermissionDenied(path),
+            _ => JournalError::Io { path, source },
+        }
+    }
+
+    /// Classify a failure to read or write the JSON in the journal at
+    /// `path`.
+    pub fn json(path: &Path, source: serde_json::Error) -> JournalError {
+        if source.is_io() {
+            return JournalError::io(path, source.into());
+        }
+        // serde_json ends its messages with the position, which is kept
+        // separately here.
+        let (line, column) = (source.line(), source.column());
+        let message = source.to_string();
+        let suffix = format!(" at line {} column {}", line, column);
+        JournalError::Corrupt {
+            path: path.to_path_buf(),
+            line,
+            column,
+            message: message
+                .strip_suffix(&suffix)
+                .unwrap_or(&message)
+                .to_string(),
+        }
+    }
+
+    /// The status `main` exits with; each kind of failure gets its own.
+    /// They start at 3, since the argument parser exits with 1 for a bad
+    /// command line and shells use 2 for misuse.  `--help` lists them.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            JournalError::NotFound(_) => 3,
+            JournalError::PermissionDenied(_) => 4,
+            JournalError::Corrupt { .. } => 5,
+            JournalError::InvalidTaskId { .. } => 6,
+            JournalError::LockContention { .. } => 7,
+            JournalError::Config(_) => 8,
+            JournalError::Io { .. } => 9,
+        }
+    }
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JournalError::NotFound(path) => write!(
+                f,
+                "no journal at {} yet; add a task with `journal add` to start one",
+                path.display()
+            ),
+            JournalError::PermissionDenied(path) => {
+                write!(f, "permission denied for journal {}", path.display())
+            }
+            JournalError::Corrupt {
+                path,
+                line,
+                column,
+                message,
+            } => write!(
+                f,
+                "journal {} is corrupt at line {}, column {}: {}",
+                path.display(),
+                line,
+                column,
+                message
+            ),
+            JournalError::InvalidTaskId { position, count } => match count {
+                0 => write!(f, "no task {}; the task list is empty", position),
+                _ => write!(f, "no task {}; tasks are numbered 1 to {}", position, count),
+            },
+            JournalError::LockContention { path, timeout } => write!(
+                f,
+                "journal {} is in use by another journal command; gave up after {:?}",
+                path.display(),
+                timeout
+            ),
+            JournalError::Config(message) => write!(f, "{}", message),
+            JournalError::Io { path, source } => write!(f, "{}: {}", path.display(), source),
+        }
+    }
+}
+
+impl std::error::Error for JournalError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            JournalError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn corrupt(text: &str) -> (usize, usize, String) {
+        let source = serde_json::from_str::<serde_json::Value>(text).unwrap_err();
+        match JournalError::json(Path::new("journal.json"), source) {
+            JournalError::Corrupt {
+                line,
+                column,
+                message,
+                ..
+            } => (line, column, message),
+            other => panic!("expected a corrupt journal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_corrupt_journal() {
+        // Cut off part way through a task.
+        let (line, column, message) = corrupt("[\n  {\"text\": \"a\"");
+        assert_eq!((line, column), (2, 14));
+        assert_eq!(message, "EOF while parsing an object");
+        // A missing colon.
+        let (line, column, message) = corrupt("[\n  {\"text\" \"a\"}\n]");
+        assert_eq!((line, column), (2, 11));
+        assert_eq!(message, "expected `:`");
+    }
+
+    #[test]
+    fn test_exit_codes_are_distinct() {
+        let path = PathBuf::from("journal.json");
+        let errors = [
+            JournalError::NotFound(path.clone()),
+            JournalError::PermissionDenied(path.clone()),
+            JournalError::Corrupt {
+                path: path.clone(),
+                line: 1,
+                column: 1,
+                message: String::new(),
+            },
+            JournalError::InvalidTaskId {
+                position: 1,
+                count: 0,
+            },
+            JournalError::LockContention {
+                path: path.clone(),
+                timeout: Duration::from_secs(1),
+            },
+            JournalError::Config(String::new()),
+            JournalError::Io {
+                path,
+                source: io::Error::other("disk full"),
+            },
+        ];
+        let mut codes: Vec<i32> = errors.iter().map(JournalError::exit_code).collect();
+        // 1 and 2 are left to the argument parser and the shell.
+        assert!(codes.iter().all(|&code| code > 2));
+        codes.sort_unstable();
+        codes.dedup();
+        assert_eq!(codes.len(), errors.len());
+    }
+}
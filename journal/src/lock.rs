@@ -0,0 +1,167 @@
+use std::fs::{self, File, OpenOptions, TryLockError};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crate::config::create_parent_dirs;
+use crate::error::JournalError;
+
+/// How long to wait for a lock unless configured otherwise.
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+// How often to retry while another command holds the lock.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Mode {
+    /// For commands that only read the journal; any number can share it.
+    Shared,
+    /// For commands that change an existing journal.
+    Exclusive,
+    /// Exclusive, for commands that may start a new journal, along with
+    /// its directory.
+    Create,
+}
+
+/// An advisory lock on a journal, held until it's dropped.
+///
+/// The lock is taken on `path` + `.lock` rather than on the journal itself,
+/// because writes replace the journal with a new file.  Readers don't
+/// create the lock file, and hold no lock if it doesn't exist yet.
+#[derive(Debug)]
+pub struct JournalLock {
+    _file: Option<File>,
+}
+
+/// The file that locks on `journal_path` are taken on.
+pub fn lock_path(journal_path: &Path) -> PathBuf {
+    let mut name = journal_path.as_os_str().to_os_string();
+    name.push(".lock");
+    PathBuf::from(name)
+}
+
+/// Lock the journal at `journal_path`, waiting up to `timeout` for other
+/// journal commands to finish with it.  A timeout too long to count down
+/// waits for as long as it takes.
+///
+/// Only `Mode::Create` creates anything on disk.  A shared lock on a journal
+/// that has never been locked isn't needed, since writes replace the
+/// journal in one step, and an exclusive lock on a missing journal fails
+/// with `NotFound`.
+pub fn acquire(
+    journal_path: &Path,
+    mode: Mode,
+    timeout: Duration,
+) -> Result<JournalLock, JournalError> {
+    let opened = match mode {
+        Mode::Shared => OpenOptions::new().read(true).open(lock_path(journal_path)),
+        Mode::Exclusive => fs::metadata(journal_path).and_then(|_| open_for_writing(journal_path)),
+        Mode::Create => {
+            create_parent_dirs(journal_path).and_then(|()| open_for_writing(journal_path))
+        }
+    };
+    let file = match opened {
+        Ok(file) => file,
+        Err(e) if mode == Mode::Shared && e.kind() == io::ErrorKind::NotFound => {
+            return Ok(JournalLock { _file: None })
+        }
+        Err(e) => return Err(JournalError::io(journal_path, e)),
+    };
+
+    let deadline = Instant::now().checked_add(timeout);
+    loop {
+        let locked = match mode {
+            Mode::Shared => file.try_lock_shared(),
+            Mode::Exclusive | Mode::Create => file.try_lock(),
+        };
+        match locked {
+            Ok(()) => return Ok(JournalLock { _file: Some(file) }),
+            Err(TryLockError::WouldBlock) if deadline.is_none_or(|d| Instant::now() < d) => {
+                thread::sleep(POLL_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(JournalError::LockContention {
+                    path: journal_path.to_path_buf(),
+                    timeout,
+                })
+            }
+            Err(TryLockError::Error(e)) => return Err(JournalError::io(journal_path, e)),
+        }
+    }
+}
+
+// Open the lock file for an exclusive lock, creating it if need be.
+fn open_for_writing(journal_path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(journal_path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SHORT: Duration = Duration::from_millis(50);
+
+    #[test]
+    fn test_shared_locks_coexist() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        drop(acquire(&path, Mode::Create, SHORT).unwrap());
+        fs::write(&path, "[]").unwrap();
+        let _first = acquire(&path, Mode::Shared, SHORT).unwrap();
+        let _second = acquire(&path, Mode::Shared, SHORT).unwrap();
+        match acquire(&path, Mode::Exclusive, SHORT) {
+            Err(JournalError::LockContention { timeout, .. }) => assert_eq!(timeout, SHORT),
+            other => panic!("expected lock contention, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_exclusive_lock_excludes() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("new").join("journal.json");
+        let lock = acquire(&path, Mode::Create, SHORT).unwrap();
+        assert!(lock_path(&path).exists());
+        fs::write(&path, "[]").unwrap();
+        for &mode in &[Mode::Shared, Mode::Exclusive, Mode::Create] {
+            match acquire(&path, mode, SHORT) {
+                Err(JournalError::LockContention { .. }) => {}
+                other => panic!("expected lock contention, got {:?}", other),
+            }
+        }
+
+        // A waiting command gets the lock once it's released, even with a
+        // timeout too long to count down.
+        let waiter = thread::spawn({
+            let path = path.clone();
+            move || acquire(&path, Mode::Exclusive, Duration::MAX).is_ok()
+        });
+        thread::sleep(SHORT);
+        drop(lock);
+        assert!(waiter.join().unwrap());
+    }
+
+    #[test]
+    fn test_missing_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        for path in &[
+            dir.path().join("journal.json"),
+            dir.path().join("missing").join("journal.json"),
+        ] {
+            // Reading needs no lock, and changing fails, without leaving
+            // anything behind.
+            assert!(acquire(path, Mode::Shared, SHORT).is_ok());
+            match acquire(path, Mode::Exclusive, SHORT) {
+                Err(JournalError::NotFound(missing)) => assert_eq!(&missing, path),
+                other => panic!("expected not found, got {:?}", other),
+            }
+            assert!(!lock_path(path).exists());
+        }
+        assert!(!dir.path().join("missing").exists());
+    }
+}
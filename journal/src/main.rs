@@ -1,25 +1,64 @@
+use std::path::PathBuf;
 use structopt::StructOpt;
 mod cli;
+mod config;
+mod error;
+mod lock;
+mod storage;
 mod tasks;
 
 use cli::{Action::*, CommandLineArgs};
-use tasks::Task;
+use config::Config;
+use error::JournalError;
+use lock::Mode;
+use tasks::{Status, Task};
 
 fn main() {
     // Get the command-line arguments.
     let CommandLineArgs {
         action,
         journal_file,
+        lock_timeout,
     } = CommandLineArgs::from_args();
 
-    // Unpack the journal file.
-    let journal_file = journal_file.expect("Failed to find journal file");
+    if let Err(e) = run(action, journal_file, lock_timeout) {
+        eprintln!("Error: {}", e);
+        std::process::exit(e.exit_code());
+    }
+}
+
+fn run(
+    action: cli::Action,
+    journal_file: Option<PathBuf>,
+    lock_timeout: Option<f64>,
+) -> Result<(), JournalError> {
+    let config = Config::load()?;
+    let journal_file = config::journal_path(journal_file, &config)?;
+    let lock_timeout = config::lock_timeout(lock_timeout, &config)?;
+
+    // Lock the journal so that concurrent commands don't lose each other's
+    // changes.  Only `list` can share it, and only `add` may start a new
+    // journal.
+    let mode = match action {
+        List { .. } => Mode::Shared,
+        Add { .. } => Mode::Create,
+        Done { .. } | Undo { .. } | Purge { .. } => Mode::Exclusive,
+    };
+    let _lock = lock::acquire(&journal_file, mode, lock_timeout)?;
 
-    // Perform the action.
+    // Perform the action.  Only `add` starts a new journal; the others
+    // report a missing one.
     match action {
         Add { text } => tasks::add_task(journal_file, Task::new(text)),
-        List => tasks::list_tasks(journal_file),
+        List { done: false } => tasks::list_tasks(journal_file, Status::Open),
+        List { done: true } => tasks::list_tasks(journal_file, Status::Done),
         Done { position } => tasks::complete_task(journal_file, position),
+        Undo { position } => tasks::undo_task(journal_file, position),
+        Purge { older_than } => {
+            let older_than = chrono::Duration::try_days(older_than.into()).ok_or_else(|| {
+                JournalError::Config(format!("can't purge tasks {} days old", older_than))
+            })?;
+            tasks::purge_tasks(journal_file, older_than)
+        }
     }
-    .expect("Failed to perform action")
 }
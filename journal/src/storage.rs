@@ -0,0 +1,165 @@
+use std::ffi::OsString;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// Replace the contents of `path` with `contents`, so that a crash or a
+/// full disk leaves either the old file or the new one, never a mix.
+///
+/// The new contents go to a temporary file in the same directory, which is
+/// synced and then renamed over `path`.  The version being replaced is
+/// kept as `path` + `.bak`, replacing the previous backup.
+pub fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    write_with(path, |file| file.write_all(contents))
+}
+
+/// The backup of the version of `path` before the last write.
+pub fn backup_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".bak")
+}
+
+// The temporary file is hidden and named after this process, so that
+// concurrent writers don't share one.
+fn temp_path(path: &Path) -> PathBuf {
+    let mut name = OsString::from(".");
+    name.push(path.file_name().unwrap_or_default());
+    name.push(format!(".tmp-{}", std::process::id()));
+    path.with_file_name(name)
+}
+
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+// write_atomically(), with the writing done by `write`.
+fn write_with<F>(path: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let temp = temp_path(path);
+    let result = write_temp(&temp, write).and_then(|()| replace(path, &temp));
+    if result.is_err() {
+        // Leave the original alone, and don't leave half a journal lying
+        // around.
+        let _ = fs::remove_file(&temp);
+    }
+    result
+}
+
+fn write_temp<F>(temp: &Path, write: F) -> io::Result<()>
+where
+    F: FnOnce(&mut File) -> io::Result<()>,
+{
+    let mut file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(temp)?;
+    write(&mut file)?;
+    file.sync_all()
+}
+
+// Move `temp` over `path`, keeping the old `path` as the backup.
+fn replace(path: &Path, temp: &Path) -> io::Result<()> {
+    let backup = backup_path(path);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    // Linking rather than renaming means `path` is never missing.
+    match fs::hard_link(path, &backup) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(_) => {
+            fs::copy(path, &backup)?;
+        }
+    }
+    fs::rename(temp, path)?;
+    sync_dir(path)
+}
+
+// Make the rename itself durable.
+#[cfg(unix)]
+fn sync_dir(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => File::open(dir)?.sync_all(),
+        _ => File::open(".")?.sync_all(),
+    }
+}
+
+#[cfg(not(unix))]
+fn sync_dir(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entries(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotating_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        write_atomically(&path, b"one").unwrap();
+        assert_eq!(entries(dir.path()), ["journal.json"]);
+        write_atomically(&path, b"two").unwrap();
+        write_atomically(&path, b"three").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "two");
+        assert_eq!(entries(dir.path()), ["journal.json", "journal.json.bak"]);
+    }
+
+    #[test]
+    fn test_partial_write_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        write_atomically(&path, b"[1, 2, 3]").unwrap();
+
+        // The disk fills up halfway through the new contents.
+        let err = write_with(&path, |file| {
+            file.write_all(b"[1, 2")?;
+            Err(io::Error::other("No space left on device"))
+        })
+        .unwrap_err();
+        assert_eq!(err.to_string(), "No space left on device");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "[1, 2, 3]");
+        assert_eq!(entries(dir.path()), ["journal.json"]);
+    }
+
+    #[test]
+    fn test_crash_before_rename() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        write_atomically(&path, b"old").unwrap();
+
+        // A process that died mid-write leaves its temporary file behind;
+        // the journal is untouched, and the next write still works.
+        fs::write(temp_path(&path), b"ne").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "old");
+        write_atomically(&path, b"new").unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+        assert_eq!(fs::read_to_string(backup_path(&path)).unwrap(), "old");
+    }
+
+    #[test]
+    fn test_failed_rename_keeps_original() {
+        let dir = tempfile::tempdir().unwrap();
+        // The journal's path is a directory, so the rename fails.
+        let path = dir.path().join("journal.json");
+        fs::create_dir(&path).unwrap();
+        assert!(write_atomically(&path, b"new").is_err());
+        assert!(path.is_dir());
+        assert!(!temp_path(&path).exists());
+    }
+}
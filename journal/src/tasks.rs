@@ -1,106 +1,281 @@
 
 use std::fmt;
 use std::fs::{OpenOptions, File};
-use std::io::{Error, ErrorKind, Result, Seek, SeekFrom};  // Include the `Error` type.
-use std::path::PathBuf;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
 
-use chrono::{serde::ts_seconds, DateTime, Local, Utc};
+use crate::error::JournalError;
+use crate::storage::write_atomically;
+
+type Result<T> = std::result::Result<T, JournalError>;
+
+use chrono::{serde::ts_seconds, serde::ts_seconds_option, DateTime, Duration, Local, Utc};
 use serde::Deserialize;
 use serde::Serialize;
 
 
+#[derive(Clone, Copy, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    #[default]
+    Open,
+    Done,
+}
+
 #[derive(Debug, Deserialize, Serialize)]
 pub struct Task {
     pub text: String,
 
     #[serde(with = "ts_seconds")]
     pub created_at: DateTime<Utc>,
+
+    // Journals written before tasks could be finished have neither field,
+    // and all of their tasks are open.
+    #[serde(default)]
+    pub status: Status,
+
+    #[serde(default, with = "ts_seconds_option")]
+    pub completed_at: Option<DateTime<Utc>>,
 }
 
 impl Task {
     pub fn new(text: String) -> Task {
         let created_at: DateTime<Utc> = Utc::now();
-        Task { text, created_at }
+        Task {
+            text,
+            created_at,
+            status: Status::Open,
+            completed_at: None,
+        }
     }
 }
 
 impl fmt::Display for Task {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let created_at = self.created_at.with_timezone(&Local).format("%F %H:%M");
-        write!(f, "{:<50} [{}]", self.text, created_at)
+        write!(f, "{:<50} [{}]", self.text, created_at)?;
+        if let Some(completed_at) = self.completed_at {
+            let completed_at = completed_at.with_timezone(&Local).format("%F %H:%M");
+            write!(f, " done [{}]", completed_at)?;
+        }
+        Ok(())
     }
 }
 
-fn collect_tasks(mut file: &File) -> Result<Vec<Task>> {
-    file.seek(SeekFrom::Start(0))?; // Rewind the file before.
+fn open(journal_path: &Path, options: &OpenOptions) -> Result<File> {
+    options
+        .open(journal_path)
+        .map_err(|e| JournalError::io(journal_path, e))
+}
+
+fn collect_tasks(journal_path: &Path, mut file: &File) -> Result<Vec<Task>> {
+    let io_error = |e| JournalError::io(journal_path, e);
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?; // Rewind the file before.
+    // An empty file is an empty journal, but one that stops partway
+    // through is corrupt.
+    let empty = file.metadata().map_err(io_error)?.len() == 0;
     let tasks = match serde_json::from_reader(file) {
         Ok(tasks) => tasks,
-        Err(e) if e.is_eof() => Vec::new(),
-        Err(e) => Err(e)?,
+        Err(e) if e.is_eof() && empty => Vec::new(),
+        Err(e) => return Err(JournalError::json(journal_path, e)),
     };
-    file.seek(SeekFrom::Start(0))?; // Rewind the file after.
+    file.seek(SeekFrom::Start(0)).map_err(io_error)?; // Rewind the file after.
     Ok(tasks)
 }
 
+fn load_tasks(journal_path: &Path) -> Result<Vec<Task>> {
+    let file = open(journal_path, OpenOptions::new().read(true))?;
+    collect_tasks(journal_path, &file)
+}
 
-pub fn add_task(journal_path: PathBuf, task: Task) -> Result<()> {
-    // Open the file.
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(journal_path)?;
+// The index of the task shown at `position` in the list of tasks with
+// `status`.  Positions start at 1, as `list` shows them.
+fn task_index(tasks: &[Task], status: Status, position: usize) -> Result<usize> {
+    let indexes: Vec<usize> = (0..tasks.len())
+        .filter(|&i| tasks[i].status == status)
+        .collect();
+    position
+        .checked_sub(1)
+        .and_then(|i| indexes.get(i).copied())
+        .ok_or(JournalError::InvalidTaskId {
+            position,
+            count: indexes.len(),
+        })
+}
 
-    // Consume the file's contents as a vector of tasks.
-    let mut tasks: Vec<Task> = collect_tasks(&file)?;
+// Replace the journal with `tasks`, atomically.
+fn save_tasks(journal_path: &Path, tasks: &[Task]) -> Result<()> {
+    let contents = serde_json::to_vec(tasks).map_err(|e| JournalError::json(journal_path, e))?;
+    write_atomically(journal_path, &contents).map_err(|e| JournalError::io(journal_path, e))
+}
+
+pub fn add_task(journal_path: PathBuf, task: Task) -> Result<()> {
+    // Consume the file's contents as a vector of tasks.  A missing
+    // journal is started afresh; taking the lock with `Mode::Create` has
+    // already made its directory.
+    let mut tasks: Vec<Task> = match open(&journal_path, OpenOptions::new().read(true)) {
+        Ok(file) => collect_tasks(&journal_path, &file)?,
+        Err(JournalError::NotFound(_)) => Vec::new(),
+        Err(e) => return Err(e),
+    };
 
     // Write the modified task list back into the file.
     tasks.push(task);
-    serde_json::to_writer(file, &tasks)?;
-
-    Ok(())
+    save_tasks(&journal_path, &tasks)
 }
 
 pub fn complete_task(journal_path: PathBuf, task_position: usize) -> Result<()> {
-    // Open the file.
-    let file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .open(journal_path)?;
-
     // Consume the file's contents as a vector of tasks.
-    let mut tasks: Vec<Task> = collect_tasks(&file)?;
+    let mut tasks: Vec<Task> = load_tasks(&journal_path)?;
 
-    // Remove the task.
-    if task_position == 0 || task_position > tasks.len() {
-        return Err(Error::new(ErrorKind::InvalidInput, "Invalid Task ID"));
-    }
-    tasks.remove(task_position - 1);
-
-    // Truncate the file.
-    file.set_len(0)?;
+    // Mark the task as finished, keeping it for `list --done`.
+    let index = task_index(&tasks, Status::Open, task_position)?;
+    tasks[index].status = Status::Done;
+    tasks[index].completed_at = Some(Utc::now());
 
     // Write the modified task list back into the file.
-    serde_json::to_writer(file, &tasks)?;
+    save_tasks(&journal_path, &tasks)
+}
+
+pub fn undo_task(journal_path: PathBuf, task_position: usize) -> Result<()> {
+    let mut tasks: Vec<Task> = load_tasks(&journal_path)?;
+
+    // Reopen a finished task; its position is the one `list --done` shows.
+    let index = task_index(&tasks, Status::Done, task_position)?;
+    tasks[index].status = Status::Open;
+    tasks[index].completed_at = None;
+
+    save_tasks(&journal_path, &tasks)
+}
+
+pub fn purge_tasks(journal_path: PathBuf, older_than: Duration) -> Result<()> {
+    let mut tasks: Vec<Task> = load_tasks(&journal_path)?;
+
+    // Drop tasks finished before the cutoff.  A finished task with no
+    // completion time can't be dated, so it goes too.  A cutoff earlier
+    // than any time chrono can hold keeps every dated task.
+    let cutoff = Utc::now()
+        .checked_sub_signed(older_than)
+        .unwrap_or(DateTime::<Utc>::MIN_UTC);
+    let count = tasks.len();
+    tasks.retain(|task| {
+        task.status == Status::Open || task.completed_at.is_some_and(|at| at >= cutoff)
+    });
+    let purged = count - tasks.len();
+
+    if purged > 0 {
+        save_tasks(&journal_path, &tasks)?;
+    }
+    println!("Purged {} completed task(s).", purged);
     Ok(())
 }
 
-pub fn list_tasks(journal_path: PathBuf) -> Result<()> {
-    // Open the file.
-    let file = OpenOptions::new().read(true).open(journal_path)?;
-    // Parse the file and collect the tasks.
-    let tasks = collect_tasks(&file)?;
+pub fn list_tasks(journal_path: PathBuf, status: Status) -> Result<()> {
+    // Parse the file and collect the tasks with this status.
+    let tasks = load_tasks(&journal_path)?;
+    let tasks: Vec<&Task> = tasks.iter().filter(|task| task.status == status).collect();
 
     // Enumerate and display tasks, if any.
     if tasks.is_empty() {
-        println!("Task list is empty!");
+        match status {
+            Status::Open => println!("Task list is empty!"),
+            Status::Done => println!("No tasks have been completed."),
+        }
     } else {
-        let mut order: u32 = 1;
-        for task in tasks {
+        for (order, task) in (1..).zip(tasks) {
             println!("{}: {}", order, task);
-            order += 1;
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn journal() -> (tempfile::TempDir, PathBuf) {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("journal.json");
+        (dir, path)
+    }
+
+    fn statuses(path: &Path) -> Vec<(String, Status, bool)> {
+        load_tasks(path)
+            .unwrap()
+            .into_iter()
+            .map(|task| (task.text, task.status, task.completed_at.is_some()))
+            .collect()
+    }
+
+    #[test]
+    fn test_old_journal_loads() {
+        let (_dir, path) = journal();
+        std::fs::write(&path, r#"[{"text":"old","created_at":1600000000}]"#).unwrap();
+        assert_eq!(
+            statuses(&path),
+            [(String::from("old"), Status::Open, false)]
+        );
+        complete_task(path.clone(), 1).unwrap();
+        assert_eq!(statuses(&path), [(String::from("old"), Status::Done, true)]);
+    }
+
+    #[test]
+    fn test_done_and_undo() {
+        let (_dir, path) = journal();
+        for text in &["a", "b", "c"] {
+            add_task(path.clone(), Task::new(text.to_string())).unwrap();
+        }
+        // Positions count open tasks for `done`, and finished ones for
+        // `undo`.
+        complete_task(path.clone(), 2).unwrap();
+        complete_task(path.clone(), 2).unwrap();
+        let done: Vec<bool> = statuses(&path)
+            .iter()
+            .map(|t| t.1 == Status::Done)
+            .collect();
+        assert_eq!(done, [false, true, true]);
+        assert!(matches!(
+            complete_task(path.clone(), 2),
+            Err(JournalError::InvalidTaskId {
+                position: 2,
+                count: 1
+            })
+        ));
+
+        undo_task(path.clone(), 2).unwrap();
+        assert_eq!(statuses(&path)[2], (String::from("c"), Status::Open, false));
+        assert!(matches!(
+            undo_task(path.clone(), 0),
+            Err(JournalError::InvalidTaskId {
+                position: 0,
+                count: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_purge() {
+        let (_dir, path) = journal();
+        let mut tasks: Vec<Task> = ["open", "recent", "old"]
+            .iter()
+            .map(|text| Task::new(text.to_string()))
+            .collect();
+        tasks[1].status = Status::Done;
+        tasks[1].completed_at = Some(Utc::now() - Duration::days(2));
+        tasks[2].status = Status::Done;
+        tasks[2].completed_at = Some(Utc::now() - Duration::days(40));
+        save_tasks(&path, &tasks).unwrap();
+
+        purge_tasks(path.clone(), Duration::days(30)).unwrap();
+        let texts: Vec<String> = statuses(&path).into_iter().map(|t| t.0).collect();
+        assert_eq!(texts, ["open", "recent"]);
+        // A cutoff too far back to represent purges nothing.
+        purge_tasks(path.clone(), Duration::days(4_000_000_000)).unwrap();
+        let texts: Vec<String> = statuses(&path).into_iter().map(|t| t.0).collect();
+        assert_eq!(texts, ["open", "recent"]);
+        purge_tasks(path.clone(), Duration::days(0)).unwrap();
+        let texts: Vec<String> = statuses(&path).into_iter().map(|t| t.0).collect();
+        assert_eq!(texts, ["open"]);
+    }
+}
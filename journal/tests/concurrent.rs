@@ -0,0 +1,120 @@
+// Run several journal processes against the same journal at once.
+
+use std::path::Path;
+use std::process::{Command, Output};
+use std::thread;
+
+use serde_json::Value;
+
+fn journal(dir: &Path, args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_journal"))
+        .arg("--journal-file")
+        .arg(dir.join("journal.json"))
+        .args(args)
+        // Keep the user's own config file out of it.
+        .env("XDG_CONFIG_HOME", dir)
+        .output()
+        .expect("Failed to run journal")
+}
+
+fn saved_tasks(dir: &Path) -> Vec<Value> {
+    let text = std::fs::read_to_string(dir.join("journal.json")).unwrap();
+    serde_json::from_str(&text).unwrap()
+}
+
+fn saved_texts(dir: &Path) -> Vec<String> {
+    saved_tasks(dir)
+        .iter()
+        .map(|task| task["text"].as_str().unwrap().to_string())
+        .collect()
+}
+
+#[test]
+fn concurrent_writers_lose_nothing() {
+    let dir = tempfile::tempdir().unwrap();
+    let writers: Vec<_> = (0..8)
+        .map(|writer| {
+            let dir = dir.path().to_path_buf();
+            thread::spawn(move || {
+                for task in 0..10 {
+                    let text = format!("writer {} task {}", writer, task);
+                    let output = journal(&dir, &["add", &text]);
+                    assert!(output.status.success(), "{:?}", output);
+                }
+            })
+        })
+        .collect();
+    // Readers run alongside, and always see a whole journal.
+    for _ in 0..10 {
+        let output = journal(dir.path(), &["list"]);
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            output.status.success() || stderr.contains("no journal"),
+            "{}",
+            stderr
+        );
+    }
+    for writer in writers {
+        writer.join().unwrap();
+    }
+
+    let mut tasks = saved_texts(dir.path());
+    assert_eq!(tasks.len(), 80);
+    tasks.sort();
+    tasks.dedup();
+    assert_eq!(tasks.len(), 80);
+}
+
+#[test]
+fn concurrent_done_marks_each_task_once() {
+    let dir = tempfile::tempdir().unwrap();
+    for task in 0..20 {
+        assert!(journal(dir.path(), &["add", &format!("task {}", task)])
+            .status
+            .success());
+    }
+    let removers: Vec<_> = (0..4)
+        .map(|_| {
+            let dir = dir.path().to_path_buf();
+            thread::spawn(move || {
+                for _ in 0..5 {
+                    assert!(journal(&dir, &["done", "1"]).status.success());
+                }
+            })
+        })
+        .collect();
+    for remover in removers {
+        remover.join().unwrap();
+    }
+    let tasks = saved_tasks(dir.path());
+    assert_eq!(tasks.len(), 20);
+    assert!(tasks.iter().all(|task| task["status"] == "done"));
+}
+
+#[test]
+fn lock_timeout_is_reported() {
+    use std::fs::OpenOptions;
+
+    let dir = tempfile::tempdir().unwrap();
+    assert!(journal(dir.path(), &["add", "first"]).status.success());
+
+    // Another process holds the journal.
+    let lock = OpenOptions::new()
+        .write(true)
+        .open(dir.path().join("journal.json.lock"))
+        .unwrap();
+    lock.lock().unwrap();
+
+    let output = journal(dir.path(), &["--lock-timeout", "0.1", "add", "second"]);
+    assert_eq!(output.status.code(), Some(7));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("in use by another journal command"),
+        "{}",
+        stderr
+    );
+
+    drop(lock);
+    assert!(journal(dir.path(), &["add", "second"]).status.success());
+    assert_eq!(saved_texts(dir.path()), ["first", "second"]);
+}